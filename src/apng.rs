@@ -0,0 +1,1316 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+use serde::Serialize;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    idat::{self, FilterStrategy, RawImage, Scanlines},
+    ihdr::{ColorType, Ihdr, InterlaceMethod},
+    png::Png,
+    standard::{Color, PaletteEntry, StandardChunk, Transparency},
+    validate::Violation,
+};
+
+/// Animation control, before the first `IDAT`
+pub const ACTL: &str = "acTL";
+/// Frame control, one for every frame
+pub const FCTL: &str = "fcTL";
+/// Frame data of every frame but the default image
+pub const FDAT: &str = "fdAT";
+
+/// Largest image converted to RGBA, 512 MiB at 16 bits per sample
+const MAX_PIXELS: usize = 64 * 1024 * 1024;
+
+/// The `acTL` chunk, marking a PNG as animated
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    /// How often to play the animation, 0 for forever
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = self.num_frames.to_be_bytes().to_vec();
+        data.extend(self.num_plays.to_be_bytes());
+
+        Chunk::new(ChunkType::from_str(ACTL).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for AnimationControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        let data = expect(chunk, ACTL, 8)?;
+
+        Ok(Self {
+            num_frames: u32_at(data, 0),
+            num_plays: u32_at(data, 4),
+        })
+    }
+}
+
+/// What happens to the frame area before the next frame is rendered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum DisposeOp {
+    /// Leave it as it is
+    None = 0,
+    /// Clear it to fully transparent black
+    Background = 1,
+    /// Restore what was there before the frame
+    Previous = 2,
+}
+
+impl TryFrom<u8> for DisposeOp {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            _ => bail!("invalid dispose op {value}"),
+        })
+    }
+}
+
+impl Display for DisposeOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisposeOp::None => write!(f, "none"),
+            DisposeOp::Background => write!(f, "background"),
+            DisposeOp::Previous => write!(f, "previous"),
+        }
+    }
+}
+
+/// How the frame is combined with what is already rendered
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum BlendOp {
+    /// Replace the frame area, alpha included
+    Source = 0,
+    /// Alpha composite the frame over the frame area
+    Over = 1,
+}
+
+impl TryFrom<u8> for BlendOp {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            _ => bail!("invalid blend op {value}"),
+        })
+    }
+}
+
+impl Display for BlendOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlendOp::Source => write!(f, "source"),
+            BlendOp::Over => write!(f, "over"),
+        }
+    }
+}
+
+/// The `fcTL` chunk, size, position and timing of a frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    /// Denominator of the delay, 0 means 100
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    /// How long the frame is shown, in seconds
+    pub fn delay(&self) -> f64 {
+        let den = match self.delay_den {
+            0 => 100,
+            den => den,
+        };
+
+        f64::from(self.delay_num) / f64::from(den)
+    }
+
+    /// Checks the frame lies within the image
+    pub fn validate(&self, ihdr: &Ihdr) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            bail!("frame is empty");
+        }
+
+        let right = u64::from(self.x_offset) + u64::from(self.width);
+        let bottom = u64::from(self.y_offset) + u64::from(self.height);
+        if right > u64::from(ihdr.width) || bottom > u64::from(ihdr.height) {
+            bail!(
+                "frame {}x{} at ({}, {}) is outside the {}x{} image",
+                self.width,
+                self.height,
+                self.x_offset,
+                self.y_offset,
+                ihdr.width,
+                ihdr.height
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = vec![];
+        for value in [
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ] {
+            data.extend(value.to_be_bytes());
+        }
+        data.extend(self.delay_num.to_be_bytes());
+        data.extend(self.delay_den.to_be_bytes());
+        data.extend([self.dispose_op as u8, self.blend_op as u8]);
+
+        Chunk::new(ChunkType::from_str(FCTL).unwrap(), data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameControl {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        let data = expect(chunk, FCTL, 26)?;
+
+        Ok(Self {
+            sequence_number: u32_at(data, 0),
+            width: u32_at(data, 4),
+            height: u32_at(data, 8),
+            x_offset: u32_at(data, 12),
+            y_offset: u32_at(data, 16),
+            delay_num: u16::from_be_bytes([data[20], data[21]]),
+            delay_den: u16::from_be_bytes([data[22], data[23]]),
+            dispose_op: DisposeOp::try_from(data[24])?,
+            blend_op: BlendOp::try_from(data[25])?,
+        })
+    }
+}
+
+impl Display for FrameControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} at ({}, {}), delay {:.3}s, dispose {}, blend {}",
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+            self.delay(),
+            self.dispose_op,
+            self.blend_op
+        )
+    }
+}
+
+/// The `fdAT` chunk, image data of a frame with a sequence number in front
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameData {
+    pub sequence_number: u32,
+    pub data: Vec<u8>,
+}
+
+impl FrameData {
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = self.sequence_number.to_be_bytes().to_vec();
+        data.extend(&self.data);
+
+        Chunk::try_new(ChunkType::from_str(FDAT)?, data)
+    }
+}
+
+impl TryFrom<&Chunk> for FrameData {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        if chunk.chunk_type().to_string() != FDAT {
+            bail!("expected an {FDAT} chunk, found {}", chunk.chunk_type());
+        }
+
+        match chunk.data().split_at_checked(4) {
+            Some((sequence_number, data)) => Ok(Self {
+                sequence_number: u32_at(sequence_number, 0),
+                data: data.to_vec(),
+            }),
+            None => bail!("{FDAT} is missing its sequence number"),
+        }
+    }
+}
+
+/// The control chunks of an animated PNG, enough to describe it without the image data
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AnimationInfo {
+    pub control: AnimationControl,
+    pub frames: Vec<FrameControl>,
+    /// Whether the first frame is the default image, shown by decoders without APNG support
+    pub default_image_is_frame: bool,
+}
+
+impl AnimationInfo {
+    /// Collects the `acTL` and `fcTL` chunks, `None` if the PNG isn't animated
+    ///
+    /// Only the chunk types matter for the other chunks, so their data may be left out.
+    pub fn from_chunks<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> Result<Option<Self>> {
+        let mut control = None;
+        let mut frames = vec![];
+        let mut default_image_is_frame = false;
+        let mut seen_idat = false;
+
+        for chunk in chunks {
+            match chunk.chunk_type().to_string().as_str() {
+                ACTL => control = Some(AnimationControl::try_from(chunk)?),
+                FCTL => {
+                    default_image_is_frame |= frames.is_empty() && !seen_idat;
+                    frames.push(FrameControl::try_from(chunk)?);
+                }
+                "IDAT" => seen_idat = true,
+                _ => {}
+            }
+        }
+
+        Ok(control.map(|control| Self {
+            control,
+            frames,
+            default_image_is_frame,
+        }))
+    }
+
+    /// Length of one play of the animation, in seconds
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(FrameControl::delay).sum()
+    }
+}
+
+impl Display for AnimationInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Animation: {} frame(s), {:.3}s, ",
+            self.frames.len(),
+            self.duration()
+        )?;
+        match self.control.num_plays {
+            0 => write!(f, "plays forever")?,
+            plays => write!(f, "plays {plays} time(s)")?,
+        }
+        if !self.default_image_is_frame {
+            write!(f, ", the default image is not part of it")?;
+        }
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            write!(f, "\nFrame {index}: {frame}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks the animation chunks of `png`, nothing is reported for PNGs without them
+///
+/// Sequence numbers of `fcTL` and `fdAT` have to count up from 0 in chunk order,
+/// every frame has to fit the image and the frame count has to match `acTL`.
+pub fn validate(png: &Png) -> Vec<Violation> {
+    let chunks = png.chunks();
+    let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+    let find = |chunk_type: &str| types.iter().position(|t| t == chunk_type);
+
+    let mut violations = vec![];
+
+    let Some(actl) = find(ACTL) else {
+        for chunk_type in [FCTL, FDAT] {
+            if let Some(index) = find(chunk_type) {
+                violations.push(Violation::error(
+                    index,
+                    format!("{chunk_type} without {ACTL}"),
+                ));
+            }
+        }
+        return violations;
+    };
+
+    let first_idat = find("IDAT");
+    if first_idat.is_some_and(|idat| actl > idat) {
+        violations.push(Violation::error(
+            actl,
+            format!("{ACTL} must come before the first IDAT"),
+        ));
+    }
+
+    let control = match AnimationControl::try_from(&chunks[actl]) {
+        Ok(control) => Some(control),
+        Err(e) => {
+            violations.push(Violation::error(actl, format!("invalid {ACTL}: {e}")));
+            None
+        }
+    };
+
+    let ihdr = png.ihdr().ok();
+    let mut next_sequence_number = 0;
+    let mut frames = 0;
+    // the last frame after the image data, and whether it got any data yet
+    let mut open_frame: Option<(usize, bool)> = None;
+
+    for (index, (chunk, chunk_type)) in chunks.iter().zip(&types).enumerate() {
+        if index > actl && chunk_type == ACTL {
+            violations.push(Violation::error(index, format!("duplicate {ACTL}")));
+        }
+
+        let sequence_number = match chunk_type.as_str() {
+            FCTL => {
+                frames += 1;
+
+                if let Some((frame, false)) = open_frame {
+                    violations.push(Violation::error(frame, "frame has no fdAT data"));
+                }
+
+                let before_idat = first_idat.is_none_or(|idat| index < idat);
+                if before_idat && frames > 1 {
+                    violations.push(Violation::error(
+                        index,
+                        "only one fcTL may come before the first IDAT",
+                    ));
+                }
+                open_frame = (!before_idat).then_some((index, false));
+
+                match FrameControl::try_from(chunk) {
+                    Ok(frame) => {
+                        check_frame(&frame, ihdr.as_ref(), before_idat, index, &mut violations);
+                        Some(frame.sequence_number)
+                    }
+                    Err(e) => {
+                        violations.push(Violation::error(index, format!("invalid {FCTL}: {e}")));
+                        None
+                    }
+                }
+            }
+            FDAT => {
+                match &mut open_frame {
+                    Some((_, has_data)) => *has_data = true,
+                    None => violations.push(Violation::error(
+                        index,
+                        "fdAT has to follow an fcTL after the image data",
+                    )),
+                }
+
+                match FrameData::try_from(chunk) {
+                    Ok(data) => Some(data.sequence_number),
+                    Err(e) => {
+                        violations.push(Violation::error(index, format!("invalid {FDAT}: {e}")));
+                        None
+                    }
+                }
+            }
+            _ => continue,
+        };
+
+        if let Some(sequence_number) = sequence_number {
+            if sequence_number != next_sequence_number {
+                violations.push(Violation::error(
+                    index,
+                    format!("sequence number {sequence_number}, expected {next_sequence_number}"),
+                ));
+            }
+            next_sequence_number = sequence_number.wrapping_add(1);
+        }
+    }
+
+    if let Some((frame, false)) = open_frame {
+        violations.push(Violation::error(frame, "frame has no fdAT data"));
+    }
+
+    if let Some(control) = control {
+        if control.num_frames == 0 {
+            violations.push(Violation::error(actl, "animation has no frames"));
+        } else if control.num_frames != frames {
+            violations.push(Violation::error(
+                actl,
+                format!(
+                    "{ACTL} announces {} frame(s), found {frames}",
+                    control.num_frames
+                ),
+            ));
+        }
+    }
+
+    violations
+}
+
+fn check_frame(
+    frame: &FrameControl,
+    ihdr: Option<&Ihdr>,
+    is_default_image: bool,
+    index: usize,
+    violations: &mut Vec<Violation>,
+) {
+    let Some(ihdr) = ihdr else {
+        return;
+    };
+
+    if let Err(e) = frame.validate(ihdr) {
+        violations.push(Violation::error(index, e.to_string()));
+    }
+
+    let covers_image = (frame.x_offset, frame.y_offset, frame.width, frame.height)
+        == (0, 0, ihdr.width, ihdr.height);
+    if is_default_image && !covers_image {
+        violations.push(Violation::error(
+            index,
+            "the default image frame has to cover the whole image",
+        ));
+    }
+}
+
+fn expect<'a>(chunk: &'a Chunk, chunk_type: &str, length: usize) -> Result<&'a [u8]> {
+    if chunk.chunk_type().to_string() != chunk_type {
+        bail!(
+            "expected an {chunk_type} chunk, found {}",
+            chunk.chunk_type()
+        );
+    }
+
+    match chunk.data().len() == length {
+        true => Ok(chunk.data()),
+        false => bail!("expected {length} bytes, found {}", chunk.data().len()),
+    }
+}
+
+fn u32_at(data: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]])
+}
+
+/// One frame of an animation, with its image data as a single zlib stream
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub control: FrameControl,
+    pub data: Vec<u8>,
+}
+
+impl Frame {
+    /// The header of the frame as a standalone image
+    fn ihdr(&self, ihdr: &Ihdr) -> Ihdr {
+        Ihdr {
+            width: self.control.width,
+            height: self.control.height,
+            ..*ihdr
+        }
+    }
+}
+
+/// An animated PNG with the image data of every frame
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub ihdr: Ihdr,
+    pub control: AnimationControl,
+    pub frames: Vec<Frame>,
+    /// Whether the first frame is the default image, shown by decoders without APNG support
+    pub default_image_is_frame: bool,
+    /// The other chunks before the image data, like `PLTE` and `gAMA`
+    pub shared: Vec<Chunk>,
+}
+
+impl Animation {
+    /// Chunks that still apply once the frames are converted to RGBA
+    const COLOR_SPACE: [&str; 5] = ["gAMA", "cHRM", "sRGB", "iCCP", "pHYs"];
+
+    pub fn read(png: &Png) -> Result<Self> {
+        let ihdr = png.ihdr()?;
+        let mut control = None;
+        let mut frames: Vec<Frame> = vec![];
+        let mut default_image_is_frame = false;
+        let mut shared = vec![];
+        let mut seen_idat = false;
+
+        for chunk in &png.chunks()[1..] {
+            match chunk.chunk_type().to_string().as_str() {
+                ACTL => control = Some(AnimationControl::try_from(chunk)?),
+                FCTL => {
+                    default_image_is_frame |= frames.is_empty() && !seen_idat;
+                    frames.push(Frame {
+                        control: FrameControl::try_from(chunk)?,
+                        data: vec![],
+                    });
+                }
+                idat::CHUNK_TYPE => {
+                    seen_idat = true;
+                    if default_image_is_frame {
+                        frames[0].data.extend(chunk.data());
+                    }
+                }
+                FDAT => match frames.last_mut() {
+                    Some(frame) if seen_idat => frame.data.extend(FrameData::try_from(chunk)?.data),
+                    _ => bail!("{FDAT} has to follow an {FCTL} after the image data"),
+                },
+                "IEND" => {}
+                _ if !seen_idat => shared.push(chunk.clone()),
+                _ => {}
+            }
+        }
+
+        let Some(control) = control else {
+            bail!("PNG is not animated, it has no {ACTL} chunk");
+        };
+
+        for (index, frame) in frames.iter().enumerate() {
+            frame.control.validate(&ihdr)?;
+            if frame.data.is_empty() {
+                bail!("frame {index} has no image data");
+            }
+        }
+
+        Ok(Self {
+            ihdr,
+            control,
+            frames,
+            default_image_is_frame,
+            shared,
+        })
+    }
+
+    /// Frame `index` as a standalone PNG, without what the previous frames left on the canvas
+    pub fn frame_png(&self, index: usize) -> Result<Png> {
+        let Some(frame) = self.frames.get(index) else {
+            bail!("Frame {index} out of range");
+        };
+
+        Ok(standalone(
+            &frame.ihdr(&self.ihdr),
+            self.shared.iter(),
+            &frame.data,
+        ))
+    }
+
+    /// Every frame as a viewer shows it, after disposing and blending the previous ones
+    ///
+    /// The frames are RGBA with a bit depth of 16 if the animation has one, 8 otherwise.
+    pub fn composite(&self) -> Result<Vec<Png>> {
+        let depth = match self.ihdr.bit_depth {
+            16 => 16,
+            _ => 8,
+        };
+        let palette = self.palette()?;
+        let transparency = self.transparency()?;
+
+        let width = self.ihdr.width as usize;
+        let mut canvas = vec![[0; 4]; pixel_count(&self.ihdr)?];
+        let mut pngs = vec![];
+
+        for (index, frame) in self.frames.iter().enumerate() {
+            let control = &frame.control;
+            let image = Scanlines::decode(frame.ihdr(&self.ihdr), &frame.data)?.to_image();
+            let pixels = to_rgba(&image, depth, palette.as_deref(), transparency.as_ref())?;
+
+            let area: Vec<usize> = (0..control.height as usize)
+                .flat_map(|y| {
+                    let start = (control.y_offset as usize + y) * width + control.x_offset as usize;
+                    start..start + control.width as usize
+                })
+                .collect();
+
+            let previous = match control.dispose_op {
+                DisposeOp::Previous => Some(canvas.clone()),
+                _ => None,
+            };
+
+            for (&i, pixel) in area.iter().zip(pixels) {
+                canvas[i] = match control.blend_op {
+                    BlendOp::Source => pixel,
+                    BlendOp::Over => blend(pixel, canvas[i], max_sample(depth)),
+                };
+            }
+
+            pngs.push(self.rgba_png(&canvas, depth)?);
+
+            match (control.dispose_op, previous) {
+                (DisposeOp::None, _) => {}
+                // the first frame has nothing to go back to, so it is cleared instead
+                (DisposeOp::Previous, Some(previous)) if index > 0 => canvas = previous,
+                _ => {
+                    for &i in &area {
+                        canvas[i] = [0; 4];
+                    }
+                }
+            }
+        }
+
+        Ok(pngs)
+    }
+
+    fn palette(&self) -> Result<Option<Vec<PaletteEntry>>> {
+        match self.shared_chunk("PLTE") {
+            Some(chunk) => match StandardChunk::parse(chunk, &self.ihdr)? {
+                StandardChunk::Palette(entries) => Ok(Some(entries)),
+                _ => unreachable!("PLTE parses as a palette"),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn transparency(&self) -> Result<Option<Transparency>> {
+        match self.shared_chunk("tRNS") {
+            Some(chunk) => match StandardChunk::parse(chunk, &self.ihdr)? {
+                StandardChunk::Transparency(transparency) => Ok(Some(transparency)),
+                _ => unreachable!("tRNS parses as transparency"),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn shared_chunk(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.shared
+            .iter()
+            .find(|c| c.chunk_type().to_string() == chunk_type)
+    }
+
+    fn rgba_png(&self, canvas: &[[u16; 4]], depth: u8) -> Result<Png> {
+        let ihdr = Ihdr {
+            bit_depth: depth,
+            color_type: ColorType::Rgba,
+            interlace_method: InterlaceMethod::None,
+            ..self.ihdr
+        };
+        let pixels = canvas
+            .iter()
+            .flatten()
+            .flat_map(|&sample| match depth {
+                16 => sample.to_be_bytes().to_vec(),
+                _ => vec![sample as u8],
+            })
+            .collect();
+
+        let data =
+            RawImage { ihdr, pixels }.encode(FilterStrategy::Adaptive, idat::DEFAULT_LEVEL)?;
+        let shared = self
+            .shared
+            .iter()
+            .filter(|c| Self::COLOR_SPACE.contains(&c.chunk_type().to_string().as_str()));
+
+        Ok(standalone(&ihdr, shared, &data))
+    }
+}
+
+/// Builds an animated PNG showing `frames` one after another
+///
+/// The frames need the same color type, bit depth, interlacing, palette and
+/// transparency. The first one is the default image and sets the canvas size,
+/// the others are placed at its top left corner. `delays` are in milliseconds, one for every frame.
+pub fn join(frames: &[Png], delays: &[u16], num_plays: u32) -> Result<Png> {
+    let Some(first) = frames.first() else {
+        bail!("no frames to join");
+    };
+    if delays.len() != frames.len() {
+        bail!("{} delay(s) for {} frame(s)", delays.len(), frames.len());
+    }
+
+    let ihdr = first.ihdr()?;
+    let palette = first.chunk_by_type("PLTE").map(Chunk::data);
+    let transparency = first.chunk_by_type("tRNS").map(Chunk::data);
+
+    let mut chunks = vec![first.chunks()[0].clone()];
+    chunks.extend(
+        first
+            .chunks()
+            .iter()
+            .skip(1)
+            .take_while(|c| c.chunk_type().to_string() != idat::CHUNK_TYPE)
+            .filter(|c| ![ACTL, FCTL, FDAT].contains(&c.chunk_type().to_string().as_str()))
+            .cloned(),
+    );
+    chunks.push(
+        AnimationControl {
+            num_frames: frames.len().try_into()?,
+            num_plays,
+        }
+        .to_chunk(),
+    );
+
+    let mut sequence_number = 0;
+    for (index, (frame, &delay)) in frames.iter().zip(delays).enumerate() {
+        let frame_ihdr = frame.ihdr()?;
+        let same_format = (
+            frame_ihdr.color_type,
+            frame_ihdr.bit_depth,
+            frame_ihdr.interlace_method,
+        ) == (ihdr.color_type, ihdr.bit_depth, ihdr.interlace_method);
+        if !same_format {
+            bail!(
+                "frame {index} has a different color type, bit depth or interlacing than frame 0"
+            );
+        }
+        if frame.chunk_by_type("PLTE").map(Chunk::data) != palette {
+            bail!("frame {index} has a different palette than frame 0");
+        }
+        if frame.chunk_by_type("tRNS").map(Chunk::data) != transparency {
+            bail!("frame {index} has a different transparency than frame 0");
+        }
+
+        let control = FrameControl {
+            sequence_number,
+            width: frame_ihdr.width,
+            height: frame_ihdr.height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: delay,
+            delay_den: 1000,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        };
+        control
+            .validate(&ihdr)
+            .map_err(|e| anyhow!("frame {index} doesn't fit: {e}"))?;
+        chunks.push(control.to_chunk());
+        sequence_number += 1;
+
+        let data = idat::image_data(frame);
+        if data.is_empty() {
+            bail!("frame {index} has no image data");
+        }
+
+        if index == 0 {
+            chunks.extend(frame.chunks_by_type(idat::CHUNK_TYPE).cloned());
+            continue;
+        }
+
+        for data in data.chunks(idat::DEFAULT_CHUNK_SIZE) {
+            let frame_data = FrameData {
+                sequence_number,
+                data: data.to_vec(),
+            };
+            chunks.push(frame_data.to_chunk()?);
+            sequence_number += 1;
+        }
+    }
+
+    chunks.push(Chunk::new(ChunkType::from_str("IEND")?, vec![]));
+
+    Ok(Png::from_chunks(chunks))
+}
+
+/// A still PNG of `data` with the given header and extra chunks
+fn standalone<'a>(ihdr: &Ihdr, shared: impl Iterator<Item = &'a Chunk>, data: &[u8]) -> Png {
+    let idat_type = ChunkType::from_str(idat::CHUNK_TYPE).unwrap();
+
+    let mut chunks = vec![ihdr.to_chunk()];
+    chunks.extend(shared.cloned());
+    chunks.extend(
+        data.chunks(idat::DEFAULT_CHUNK_SIZE)
+            .map(|d| Chunk::new(idat_type.clone(), d.to_vec())),
+    );
+    chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+
+    Png::from_chunks(chunks)
+}
+
+/// Pixels in an image described by `ihdr`, failing for images too large to convert
+fn pixel_count(ihdr: &Ihdr) -> Result<usize> {
+    match (ihdr.width as usize).checked_mul(ihdr.height as usize) {
+        Some(count) if count <= MAX_PIXELS => Ok(count),
+        _ => bail!(
+            "image of {}x{} pixels is too large",
+            ihdr.width,
+            ihdr.height
+        ),
+    }
+}
+
+fn max_sample(depth: u8) -> u16 {
+    ((1u32 << depth) - 1) as u16
+}
+
+/// Converts every pixel to RGBA samples of `depth` bits
+fn to_rgba(
+    image: &RawImage,
+    depth: u8,
+    palette: Option<&[PaletteEntry]>,
+    transparency: Option<&Transparency>,
+) -> Result<Vec<[u16; 4]>> {
+    let ihdr = &image.ihdr;
+    let channels = usize::from(ihdr.color_type.channels());
+    let (bit_depth, max) = (ihdr.bit_depth, max_sample(depth));
+    let scale = |sample: u16| match bit_depth {
+        16 => sample,
+        _ => (u32::from(sample) * u32::from(max) / u32::from(max_sample(bit_depth))) as u16,
+    };
+
+    let mut pixels = Vec::with_capacity(pixel_count(ihdr)?);
+
+    for y in 0..ihdr.height as usize {
+        let row = image.row(y);
+
+        for x in 0..ihdr.width as usize {
+            let samples: Vec<u16> = (0..channels)
+                .map(|c| sample(row, x * channels + c, bit_depth))
+                .collect();
+
+            let is_key = |color: Color| transparency == Some(&Transparency::Color(color));
+
+            pixels.push(match (ihdr.color_type, samples.as_slice()) {
+                (ColorType::Grayscale, &[gray]) => {
+                    let alpha = if is_key(Color::Gray(gray)) { 0 } else { max };
+                    [scale(gray), scale(gray), scale(gray), alpha]
+                }
+                (ColorType::GrayscaleAlpha, &[gray, alpha]) => {
+                    [scale(gray), scale(gray), scale(gray), scale(alpha)]
+                }
+                (ColorType::Rgb, &[red, green, blue]) => {
+                    let alpha = if is_key(Color::Rgb { red, green, blue }) {
+                        0
+                    } else {
+                        max
+                    };
+                    [scale(red), scale(green), scale(blue), alpha]
+                }
+                (ColorType::Rgba, &[red, green, blue, alpha]) => {
+                    [scale(red), scale(green), scale(blue), scale(alpha)]
+                }
+                (ColorType::Indexed, &[index]) => {
+                    let index = usize::from(index);
+                    let Some(entry) = palette.and_then(|p| p.get(index)) else {
+                        bail!("palette index {index} is out of range");
+                    };
+                    let alpha = match transparency {
+                        Some(Transparency::Alphas(alphas)) => alphas.get(index).copied(),
+                        _ => None,
+                    };
+
+                    [entry.red, entry.green, entry.blue, alpha.unwrap_or(255)].map(u16::from)
+                }
+                _ => unreachable!("one sample per channel"),
+            });
+        }
+    }
+
+    Ok(pixels)
+}
+
+/// Sample `index` of a row, samples below 8 bits are packed from the high bits down
+fn sample(row: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+        8 => row[index].into(),
+        bits => {
+            let per_byte = usize::from(8 / bits);
+            let shift = 8 - bits as usize * (index % per_byte + 1);
+            u16::from(row[index / per_byte] >> shift) & max_sample(bits)
+        }
+    }
+}
+
+/// Alpha composites `source` over `destination`
+fn blend(source: [u16; 4], destination: [u16; 4], max: u16) -> [u16; 4] {
+    let max = u64::from(max);
+    let (source_alpha, destination_alpha) = (u64::from(source[3]), u64::from(destination[3]));
+
+    match source_alpha {
+        0 => return destination,
+        alpha if alpha == max => return source,
+        _ => {}
+    }
+
+    let alpha = source_alpha * max + destination_alpha * (max - source_alpha);
+    let mut result = [0; 4];
+    for c in 0..3 {
+        let color = u64::from(source[c]) * source_alpha * max
+            + u64::from(destination[c]) * destination_alpha * (max - source_alpha);
+        result[c] = (color / alpha) as u16;
+    }
+    result[3] = (alpha / max) as u16;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::Position;
+
+    fn frame(sequence_number: u32, width: u32, height: u32) -> FrameControl {
+        FrameControl {
+            sequence_number,
+            width,
+            height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+        }
+    }
+
+    fn frame_data(sequence_number: u32) -> Chunk {
+        FrameData {
+            sequence_number,
+            data: vec![1, 2, 3],
+        }
+        .to_chunk()
+        .unwrap()
+    }
+
+    fn chunk(chunk_type: &str) -> Chunk {
+        Chunk::new(chunk_type.parse().unwrap(), vec![])
+    }
+
+    /// Three frames, the first one is the default image
+    fn testing_chunks() -> Vec<Chunk> {
+        let ihdr = Ihdr {
+            width: 4,
+            height: 4,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        };
+        let control = AnimationControl {
+            num_frames: 3,
+            num_plays: 0,
+        };
+
+        vec![
+            ihdr.to_chunk(),
+            control.to_chunk(),
+            frame(0, 4, 4).to_chunk(),
+            chunk("IDAT"),
+            frame(1, 2, 2).to_chunk(),
+            frame_data(2),
+            frame(3, 4, 4).to_chunk(),
+            frame_data(4),
+            frame_data(5),
+            chunk("IEND"),
+        ]
+    }
+
+    fn messages(chunks: Vec<Chunk>) -> Vec<String> {
+        validate(&Png::from_chunks(chunks))
+            .into_iter()
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let mut frame = frame(7, 3, 2);
+        frame.x_offset = 1;
+        frame.dispose_op = DisposeOp::Previous;
+        frame.blend_op = BlendOp::Over;
+
+        let chunk = frame.to_chunk();
+        assert_eq!(chunk.length(), 26);
+        assert_eq!(FrameControl::try_from(&chunk).unwrap(), frame);
+    }
+
+    #[test]
+    fn test_invalid_frame_control() {
+        let mut data = frame(0, 1, 1).to_chunk().data().to_vec();
+        data[24] = 3;
+        let invalid = Chunk::new(FCTL.parse().unwrap(), data);
+
+        assert!(FrameControl::try_from(&invalid).is_err());
+        assert!(FrameControl::try_from(&chunk("fcTL")).is_err());
+        assert!(AnimationControl::try_from(&frame(0, 1, 1).to_chunk()).is_err());
+    }
+
+    #[test]
+    fn test_frame_data_round_trip() {
+        let chunk = frame_data(9);
+        assert_eq!(chunk.data(), [0, 0, 0, 9, 1, 2, 3]);
+        assert_eq!(
+            FrameData::try_from(&chunk).unwrap(),
+            FrameData {
+                sequence_number: 9,
+                data: vec![1, 2, 3]
+            }
+        );
+        assert!(FrameData::try_from(&Chunk::new(FDAT.parse().unwrap(), vec![0, 0])).is_err());
+    }
+
+    #[test]
+    fn test_delay() {
+        let mut frame = frame(0, 1, 1);
+        assert_eq!(frame.delay(), 0.1);
+
+        frame.delay_den = 0;
+        assert_eq!(frame.delay(), 0.01);
+    }
+
+    #[test]
+    fn test_animation_info() {
+        let info = AnimationInfo::from_chunks(&testing_chunks())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(info.control.num_frames, 3);
+        assert_eq!(info.frames.len(), 3);
+        assert!(info.default_image_is_frame);
+        assert!((info.duration() - 0.3).abs() < 1e-9);
+        assert_eq!(
+            info.to_string(),
+            "Animation: 3 frame(s), 0.300s, plays forever\n\
+             Frame 0: 4x4 at (0, 0), delay 0.100s, dispose none, blend source\n\
+             Frame 1: 2x2 at (0, 0), delay 0.100s, dispose none, blend source\n\
+             Frame 2: 4x4 at (0, 0), delay 0.100s, dispose none, blend source"
+        );
+
+        let still = [chunk("IHDR"), chunk("IDAT"), chunk("IEND")];
+        assert_eq!(AnimationInfo::from_chunks(&still).unwrap(), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(messages(testing_chunks()).is_empty());
+        assert!(messages(vec![chunk("IHDR"), chunk("IDAT"), chunk("IEND")]).is_empty());
+    }
+
+    #[test]
+    fn test_validate_sequence_numbers() {
+        let mut chunks = testing_chunks();
+        chunks[5] = frame_data(3);
+
+        assert_eq!(
+            messages(chunks),
+            [
+                "error: chunk 5: sequence number 3, expected 2",
+                "error: chunk 6: sequence number 3, expected 4"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_frame_bounds() {
+        let mut chunks = testing_chunks();
+        let mut outside = frame(1, 2, 2);
+        outside.x_offset = 3;
+        chunks[4] = outside.to_chunk();
+        chunks[2] = frame(0, 2, 2).to_chunk();
+
+        assert_eq!(
+            messages(chunks),
+            [
+                "error: chunk 2: the default image frame has to cover the whole image",
+                "error: chunk 4: frame 2x2 at (3, 0) is outside the 4x4 image"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_frame_count_and_data() {
+        let mut chunks = testing_chunks();
+        chunks.remove(5);
+        chunks[1] = AnimationControl {
+            num_frames: 2,
+            num_plays: 1,
+        }
+        .to_chunk();
+
+        let messages = messages(chunks);
+        assert!(messages.contains(&"error: chunk 1: acTL announces 2 frame(s), found 3".into()));
+        assert!(messages.contains(&"error: chunk 4: frame has no fdAT data".into()));
+    }
+
+    #[test]
+    fn test_validate_without_actl() {
+        let mut chunks = testing_chunks();
+        chunks.remove(1);
+
+        assert_eq!(
+            messages(chunks),
+            [
+                "error: chunk 1: fcTL without acTL",
+                "error: chunk 4: fdAT without acTL"
+            ]
+        );
+    }
+
+    fn rgba_ihdr(width: u32, height: u32) -> Ihdr {
+        Ihdr {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+        let image = RawImage {
+            ihdr: rgba_ihdr(width, height),
+            pixels: color.repeat((width * height) as usize),
+        };
+        image.encode(FilterStrategy::Adaptive, 6).unwrap()
+    }
+
+    /// Red, green blended over it and then blue, with every dispose op
+    fn animated_png() -> Png {
+        let mut green = frame(1, 2, 2);
+        (green.x_offset, green.y_offset) = (1, 1);
+        green.blend_op = BlendOp::Over;
+        green.dispose_op = DisposeOp::Background;
+
+        let mut blue = frame(3, 2, 2);
+        (blue.x_offset, blue.y_offset) = (2, 2);
+        blue.dispose_op = DisposeOp::Previous;
+
+        let fdat = |sequence_number, data| FrameData {
+            sequence_number,
+            data,
+        };
+
+        Png::from_chunks(vec![
+            rgba_ihdr(4, 4).to_chunk(),
+            Chunk::new("gAMA".parse().unwrap(), 45455u32.to_be_bytes().to_vec()),
+            AnimationControl {
+                num_frames: 3,
+                num_plays: 0,
+            }
+            .to_chunk(),
+            frame(0, 4, 4).to_chunk(),
+            Chunk::new("IDAT".parse().unwrap(), solid(4, 4, [255, 0, 0, 255])),
+            green.to_chunk(),
+            fdat(2, solid(2, 2, [0, 255, 0, 128])).to_chunk().unwrap(),
+            blue.to_chunk(),
+            fdat(4, solid(2, 2, [0, 0, 255, 255])).to_chunk().unwrap(),
+            chunk("IEND"),
+        ])
+    }
+
+    fn pixel(png: &Png, x: usize, y: usize) -> [u8; 4] {
+        let image = RawImage::decode(png).unwrap();
+        image.row(y)[x * 4..x * 4 + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_read_animation() {
+        let animation = Animation::read(&animated_png()).unwrap();
+
+        assert_eq!(animation.frames.len(), 3);
+        assert!(animation.default_image_is_frame);
+        assert_eq!(animation.shared.len(), 1);
+        assert_eq!(animation.frames[1].data, solid(2, 2, [0, 255, 0, 128]));
+
+        assert!(Animation::read(&Png::from_chunks(vec![rgba_ihdr(1, 1).to_chunk()])).is_err());
+    }
+
+    #[test]
+    fn test_frame_png() {
+        let animation = Animation::read(&animated_png()).unwrap();
+        let png = animation.frame_png(1).unwrap();
+
+        assert!(png.validate().is_empty());
+        assert_eq!(png.ihdr().unwrap(), rgba_ihdr(2, 2));
+        assert_eq!(pixel(&png, 1, 1), [0, 255, 0, 128]);
+        assert!(png.chunk_by_type("gAMA").is_some());
+        assert!(animation.frame_png(3).is_err());
+    }
+
+    #[test]
+    fn test_composite() {
+        let frames = Animation::read(&animated_png())
+            .unwrap()
+            .composite()
+            .unwrap();
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(pixel(&frames[0], 1, 1), [255, 0, 0, 255]);
+
+        // green at half alpha over red
+        assert_eq!(pixel(&frames[1], 0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&frames[1], 1, 1), [127, 128, 0, 255]);
+
+        // the green area was cleared after its frame
+        assert_eq!(pixel(&frames[2], 1, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&frames[2], 2, 1), [0, 0, 0, 0]);
+        assert_eq!(pixel(&frames[2], 2, 2), [0, 0, 255, 255]);
+        assert_eq!(pixel(&frames[2], 0, 3), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_composite_huge_canvas() {
+        let mut png = animated_png();
+        png.replace_chunk_at(0, rgba_ihdr(0x7fff_ffff, 0x7fff_ffff).to_chunk())
+            .unwrap();
+
+        assert!(Animation::read(&png).unwrap().composite().is_err());
+    }
+
+    #[test]
+    fn test_blend() {
+        assert_eq!(blend([0, 0, 0, 0], [1, 2, 3, 4], 255), [1, 2, 3, 4]);
+        assert_eq!(blend([1, 2, 3, 255], [9, 9, 9, 9], 255), [1, 2, 3, 255]);
+        assert_eq!(blend([255, 0, 0, 128], [0, 0, 0, 0], 255), [255, 0, 0, 128]);
+    }
+
+    #[test]
+    fn test_sample() {
+        let row = [0b1011_0001, 0x12, 0x34];
+        assert_eq!(sample(&row, 0, 1), 1);
+        assert_eq!(sample(&row, 1, 1), 0);
+        assert_eq!(sample(&row, 1, 2), 0b11);
+        assert_eq!(sample(&row, 1, 4), 1);
+        assert_eq!(sample(&row, 1, 8), 0x12);
+        assert_eq!(sample(&row, 0, 16), 0xb112);
+    }
+
+    #[test]
+    fn test_join() {
+        let frames = Animation::read(&animated_png())
+            .unwrap()
+            .composite()
+            .unwrap();
+
+        let png = join(&frames, &[100, 200, 300], 2).unwrap();
+        assert!(png.validate().is_empty());
+
+        let animation = Animation::read(&png).unwrap();
+        assert_eq!(animation.control.num_plays, 2);
+        assert!(animation.default_image_is_frame);
+        assert_eq!(animation.frames[2].control.delay(), 0.3);
+        assert_eq!(animation.frames[1].data, idat::image_data(&frames[1]));
+        assert!(png.chunk_by_type("gAMA").is_some());
+    }
+
+    #[test]
+    fn test_join_invalid() {
+        let still = |width, height| {
+            Png::from_chunks(vec![
+                rgba_ihdr(width, height).to_chunk(),
+                Chunk::new("IDAT".parse().unwrap(), solid(width, height, [0; 4])),
+                chunk("IEND"),
+            ])
+        };
+
+        assert!(join(&[], &[], 0).is_err());
+        assert!(join(&[still(2, 2)], &[1, 2], 0).is_err());
+        assert!(join(&[still(2, 2), still(3, 1)], &[1, 2], 0).is_err());
+        assert!(join(&[still(2, 2), still(1, 1)], &[1, 2], 0).is_ok());
+
+        let mut transparent = still(1, 1);
+        transparent
+            .insert_chunk(
+                Chunk::new("tRNS".parse().unwrap(), vec![0, 0, 0, 0, 0, 0]),
+                Position::BeforeIdat,
+            )
+            .unwrap();
+        assert!(join(&[still(2, 2), transparent.clone()], &[1, 2], 0).is_err());
+        assert!(join(&[transparent.clone(), transparent], &[1, 2], 0).is_ok());
+    }
+}
@@ -1,10 +1,22 @@
-use std::{fs, path::PathBuf, str::FromStr};
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::PathBuf,
+    str::FromStr,
+};
 
 use anyhow::{anyhow, bail, Error, Result};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use reqwest::blocking;
 
-use crate::{chunk_type::ChunkType, png::Png};
+use crate::{
+    chunk_type::ChunkType,
+    exif::Tag,
+    fragment, idat,
+    png::{ChunkReader, Png, Position},
+    stego::{Channels, LsbOptions},
+    strip::ChunkPattern,
+};
 
 /// Simple CLI tool to hide messages inside a PNG
 #[derive(Debug, Parser)]
@@ -31,10 +43,37 @@ pub enum Commands {
         chunk_type: ChunkType,
 
         /// Your secret message
-        message: String,
+        #[arg(required_unless_present_any = ["file", "stdin"], conflicts_with_all = ["file", "stdin"])]
+        message: Option<String>,
 
-        /// The output for the PNG with the secret message
-        output_path: Option<PathBuf>,
+        /// Hide the content of this file instead of a message
+        #[arg(long, conflicts_with = "stdin")]
+        file: Option<PathBuf>,
+
+        /// Hide the content piped into stdin instead of a message
+        #[arg(long)]
+        stdin: bool,
+
+        /// The output for the PNG with the secret message, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Where to place the chunk: `before-iend`, `after-ihdr`, `before-idat` or `after-<index>`
+        #[arg(long, default_value = "before-iend", value_parser = Position::from_str)]
+        position: Position,
+
+        /// Encrypt the message with a key derived from this passphrase.
+        /// Prefer the environment variable, it stays out of the process list and shell history.
+        #[arg(long, env = "PNGME_PASSPHRASE", hide_env_values = true)]
+        passphrase: Option<String>,
+
+        /// Deflate the message, kept only if it gets smaller
+        #[arg(long)]
+        compress: bool,
+
+        /// Split messages larger than this across multiple chunks
+        #[arg(long, value_name = "BYTES", default_value_t = fragment::DEFAULT_MAX_CHUNK_SIZE)]
+        max_chunk_size: usize,
     },
 
     /// Decode a secret message from a PNG file
@@ -48,18 +87,198 @@ pub enum Commands {
         /// Leave empty to search for potential secret messages.
         #[arg(value_parser = chunk_type_parser)]
         chunk_type: Option<ChunkType>,
+
+        /// Passphrase for encrypted messages.
+        /// Prefer the environment variable, it stays out of the process list and shell history.
+        #[arg(long, env = "PNGME_PASSPHRASE", hide_env_values = true)]
+        passphrase: Option<String>,
+
+        /// How to print the messages
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Save the decoded content to this file instead of printing it, `-` for stdout
+        #[arg(short, long, requires = "chunk_type", conflicts_with = "all")]
+        output: Option<PathBuf>,
+
+        /// Decode every chunk of the type, not only the first one
+        #[arg(long, requires = "chunk_type")]
+        all: bool,
+
+        /// Decode the chunk of the type with this index, counting from 0
+        #[arg(long, requires = "chunk_type", conflicts_with = "all")]
+        index: Option<usize>,
     },
 
-    /// Remove chunk from PNG
+    /// Remove chunk from PNG, critical chunks like IDAT only with `--index`
     #[command(arg_required_else_help = true)]
     Remove {
         /// File path or url to a png file
         #[arg(value_parser = Input::from_str)]
         input: Input,
 
-        /// A chunk type, i.e. `ruSt`
-        #[arg(value_parser = chunk_type_parser)]
-        chunk_type: ChunkType,
+        /// A chunk type, i.e. `ruSt`, a pattern with `*` and `?`, or a chunk property like `private`.
+        /// IHDR, PLTE, IDAT and IEND are never matched, remove them with `--index`.
+        #[arg(
+            value_parser = ChunkPattern::from_str,
+            required_unless_present_any = ["index", "contains", "min_size", "max_size"]
+        )]
+        chunk_type: Option<ChunkPattern>,
+
+        /// Remove every matching chunk, not only the first one
+        #[arg(long)]
+        all: bool,
+
+        /// Remove the chunk at this index, needed for IHDR, PLTE, IDAT and IEND
+        #[arg(long, conflicts_with_all = ["chunk_type", "all", "contains", "min_size", "max_size"])]
+        index: Option<usize>,
+
+        /// Only remove chunks whose data contains this text
+        #[arg(long)]
+        contains: Option<String>,
+
+        /// Only remove chunks with at least this many bytes of data
+        #[arg(long, value_name = "BYTES")]
+        min_size: Option<usize>,
+
+        /// Only remove chunks with at most this many bytes of data
+        #[arg(long, value_name = "BYTES")]
+        max_size: Option<usize>,
+
+        /// The output for the PNG without the chunks, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check a PNG against the chunk layout rules of the spec
+    #[command(arg_required_else_help = true)]
+    Validate {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// Fail on warnings too, not only on errors
+        #[arg(long)]
+        strict: bool,
+    },
+
+    /// Fix crcs and drop unreadable chunks and trailing garbage
+    #[command(arg_required_else_help = true)]
+    Repair {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The output for the repaired PNG, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Only list the problems, don't write anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove metadata, all ancillary chunks unless told otherwise
+    #[command(arg_required_else_help = true)]
+    Strip {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The output for the stripped PNG, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Chunks to keep: types with `*` and `?`, or `ancillary`, `critical`, `private`, `safe-to-copy`, `unsafe-to-copy`
+        #[arg(long, value_delimiter = ',', value_parser = ChunkPattern::from_str)]
+        keep: Vec<ChunkPattern>,
+
+        /// Chunks to drop instead of all ancillary ones, same patterns as `--keep`
+        #[arg(long, value_delimiter = ',', value_parser = ChunkPattern::from_str)]
+        drop: Vec<ChunkPattern>,
+
+        /// Only list the chunks that would be removed, don't write anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Recompress the image data with the filters that make it smallest
+    #[command(arg_required_else_help = true)]
+    Optimize {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The output for the optimized PNG, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Highest zlib compression level to try
+        #[arg(long, default_value_t = 9, value_parser = clap::value_parser!(u32).range(0..=9))]
+        level: u32,
+
+        /// Split the image data into IDAT chunks of this size
+        #[arg(long, value_name = "BYTES", default_value_t = idat::DEFAULT_CHUNK_SIZE)]
+        idat_size: usize,
+
+        /// Only report the savings, don't write anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Read and write tEXt, zTXt and iTXt metadata
+    #[command(arg_required_else_help = true)]
+    Text {
+        #[command(subcommand)]
+        command: TextCommands,
+    },
+
+    /// Hide messages in the least significant bits of the pixels instead of a chunk
+    #[command(arg_required_else_help = true)]
+    Lsb {
+        #[command(subcommand)]
+        command: LsbCommands,
+    },
+
+    /// Split an animated PNG into frames or join PNGs into an animation
+    #[command(arg_required_else_help = true)]
+    Apng {
+        #[command(subcommand)]
+        command: ApngCommands,
+    },
+
+    /// Show or remove the Exif metadata of the eXIf chunk, like camera and GPS position
+    #[command(arg_required_else_help = true)]
+    Exif {
+        #[command(subcommand)]
+        command: ExifCommands,
+    },
+
+    /// Show the header and every standard chunk, like gamma, palette and time, readably
+    #[command(arg_required_else_help = true)]
+    Inspect {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+    },
+
+    /// Set a standard chunk, replacing the existing one, or clear it with `none`
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// What to set
+        #[arg(value_enum)]
+        field: SetField,
+
+        /// The new value, see the field for the format, or `none` to remove the chunk
+        value: String,
+
+        /// The output for the changed PNG, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
     },
 
     /// Print from PNG
@@ -68,30 +287,353 @@ pub enum Commands {
         /// File path or url to a png file
         #[arg(value_parser = Input::from_str)]
         input: Input,
+
+        /// How to print the chunks
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+
+        /// Include up to this many bytes of chunk data, hex encoded
+        #[arg(long, value_name = "BYTES")]
+        preview: Option<usize>,
+
+        /// Show offsets, lengths, stored and computed crcs and property bits of every chunk
+        #[arg(short, long)]
+        long: bool,
     },
 }
 
+#[derive(Debug, Subcommand)]
+pub enum TextCommands {
+    /// List all text chunks
+    #[command(arg_required_else_help = true)]
+    List {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+    },
+
+    /// Print the text stored under a keyword
+    #[command(arg_required_else_help = true)]
+    Get {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The keyword, i.e. `Author`
+        keyword: String,
+    },
+
+    /// Store text under a keyword, replacing any existing text
+    #[command(arg_required_else_help = true)]
+    Set {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The keyword, i.e. `Author`
+        keyword: String,
+
+        /// The text to store
+        text: String,
+
+        /// The output for the PNG with the text, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// The kind of text chunk to write
+        #[arg(long, value_enum, default_value_t = TextKind::Text)]
+        kind: TextKind,
+
+        /// Language tag of iTXt text, i.e. `en-US`
+        #[arg(long, default_value = "")]
+        language: String,
+
+        /// Keyword translated into the language of iTXt text
+        #[arg(long, default_value = "")]
+        translated_keyword: String,
+
+        /// Compress iTXt text
+        #[arg(long)]
+        compress: bool,
+    },
+
+    /// Remove all text stored under a keyword
+    #[command(arg_required_else_help = true)]
+    Delete {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The keyword, i.e. `Author`
+        keyword: String,
+
+        /// The output for the PNG without the text, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ApngCommands {
+    /// Write every frame as a standalone PNG, named `frame-000.png` and so on
+    #[command(arg_required_else_help = true)]
+    Split {
+        /// File path or url to an animated png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The directory for the frames, created if missing
+        output_dir: PathBuf,
+
+        /// Write the frame data as stored instead of rendering it onto the previous frames
+        #[arg(long)]
+        raw: bool,
+    },
+
+    /// Build an animated PNG with the given PNGs as frames, the first one is the default image
+    #[command(arg_required_else_help = true)]
+    Join {
+        /// File paths or urls to the png files, in order
+        #[arg(required = true, value_parser = Input::from_str)]
+        inputs: Vec<Input>,
+
+        /// The output for the animated PNG, `-` for stdout
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// How long each frame is shown in milliseconds, one for all frames or one per frame
+        #[arg(long, value_delimiter = ',', default_value = "100")]
+        delay: Vec<u16>,
+
+        /// How often to play the animation, 0 for forever
+        #[arg(long, default_value_t = 0)]
+        plays: u32,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExifCommands {
+    /// Print camera, orientation, timestamps and GPS position, followed by every tag
+    #[command(arg_required_else_help = true)]
+    Show {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+    },
+
+    /// Remove the GPS position and every other GPS tag
+    #[command(arg_required_else_help = true)]
+    StripGps {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The output for the PNG without GPS tags, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Remove tags, given by name like `Orientation` or number like `0x0112` or `gps:2`
+    #[command(arg_required_else_help = true)]
+    RemoveTag {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// The tags to remove, comma separated
+        #[arg(value_delimiter = ',', required = true, value_parser = Tag::from_str)]
+        tags: Vec<Tag>,
+
+        /// The output for the PNG without the tags, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum LsbCommands {
+    /// Print how many bytes fit into the pixels
+    #[command(arg_required_else_help = true)]
+    Capacity {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        #[command(flatten)]
+        lsb: LsbArgs,
+    },
+
+    /// Encode a secret message into the pixels
+    #[command(arg_required_else_help = true)]
+    Encode {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// Your secret message
+        #[arg(required_unless_present_any = ["file", "stdin"], conflicts_with_all = ["file", "stdin"])]
+        message: Option<String>,
+
+        /// Hide the content of this file instead of a message
+        #[arg(long, conflicts_with = "stdin")]
+        file: Option<PathBuf>,
+
+        /// Hide the content piped into stdin instead of a message
+        #[arg(long)]
+        stdin: bool,
+
+        /// The output for the PNG with the secret message, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Encrypt the message with a key derived from this passphrase.
+        /// Prefer the environment variable, it stays out of the process list and shell history.
+        #[arg(long, env = "PNGME_PASSPHRASE", hide_env_values = true)]
+        passphrase: Option<String>,
+
+        /// Deflate the message, kept only if it gets smaller
+        #[arg(long)]
+        compress: bool,
+
+        #[command(flatten)]
+        lsb: LsbArgs,
+    },
+
+    /// Decode a secret message from the pixels
+    #[command(arg_required_else_help = true)]
+    Decode {
+        /// File path or url to a png file
+        #[arg(value_parser = Input::from_str)]
+        input: Input,
+
+        /// Passphrase for encrypted messages.
+        /// Prefer the environment variable, it stays out of the process list and shell history.
+        #[arg(long, env = "PNGME_PASSPHRASE", hide_env_values = true)]
+        passphrase: Option<String>,
+
+        /// Save the decoded content to this file instead of printing it, `-` for stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        lsb: LsbArgs,
+    },
+}
+
+/// Has to match between encoding and decoding
+#[derive(Debug, Args)]
+pub struct LsbArgs {
+    /// Channels to hide the bits in, any of `rgba`, gray images use `r`, `g` or `b`
+    #[arg(long, default_value = "rgb", value_parser = Channels::from_str)]
+    pub channels: Channels,
+
+    /// Low bits of each sample to use
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=8))]
+    pub bits: u8,
+}
+
+impl LsbArgs {
+    pub fn options(&self) -> LsbOptions {
+        LsbOptions {
+            channels: self.channels,
+            bits: self.bits,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable
+    Text,
+    /// Stable schema for scripts
+    Json,
+    /// One row per entry
+    Table,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SetField {
+    /// pHYs, pixels per inch like `300` or `300x150`
+    Dpi,
+    /// gAMA, the file gamma like `0.45455`
+    Gamma,
+    /// sRGB, the rendering intent: `perceptual`, `relative`, `saturation` or `absolute`
+    Srgb,
+    /// cHRM, `srgb` or white, red, green and blue x,y pairs like `0.3127,0.329,0.64,...`
+    Chrm,
+    /// tIME, `now` or a UTC time like `2024-01-31 12:00:00`
+    Time,
+    /// bKGD, `#rrggbb` or comma separated samples, a palette index for indexed images
+    Background,
+}
+
+impl SetField {
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            SetField::Dpi => "pHYs",
+            SetField::Gamma => "gAMA",
+            SetField::Srgb => "sRGB",
+            SetField::Chrm => "cHRM",
+            SetField::Time => "tIME",
+            SetField::Background => "bKGD",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum TextKind {
+    /// tEXt, Latin-1 text
+    Text,
+    /// zTXt, compressed Latin-1 text
+    Ztxt,
+    /// iTXt, UTF-8 text
+    Itxt,
+}
+
+/// A png file path or url, only read once a command needs it
 #[derive(Clone, Debug)]
 pub struct Input {
-    pub png: Png,
+    pub source: String,
     pub path: Option<String>,
 }
 
+impl Input {
+    pub fn reader(&self) -> Result<Box<dyn Read>> {
+        match &self.path {
+            Some(path) => Ok(Box::new(File::open(path)?)),
+            None => Ok(Box::new(blocking::get(&self.source)?.error_for_status()?)),
+        }
+    }
+
+    /// Streams the chunks of the input without loading the whole file
+    pub fn chunks(&self) -> Result<ChunkReader<Box<dyn Read>>> {
+        ChunkReader::new(self.reader()?).map_err(|e| anyhow!("Invalid file: {e}"))
+    }
+
+    pub fn bytes(&self) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        self.reader()?.read_to_end(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    pub fn png(&self) -> Result<Png> {
+        Png::from_reader(self.reader()?).map_err(|e| anyhow!("Invalid file: {e}"))
+    }
+}
+
 impl FromStr for Input {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let is_url = s.starts_with("https");
 
-        let file_bytes = match is_url {
-            true => blocking::get(s)?.bytes()?.into(),
-            false => fs::read(s)?,
-        };
-
-        let png = Png::try_from(&file_bytes[..]).map_err(|e| anyhow!("Invalid file: {e}"))?;
+        if !is_url && !fs::metadata(s)?.is_file() {
+            bail!("{s} is not a file");
+        }
 
         Ok(Input {
-            png,
+            source: s.to_string(),
             path: (!is_url).then_some(s.to_string()),
         })
     }
@@ -1,6 +1,6 @@
 use std::{
     fmt::Display,
-    io::{BufReader, Read},
+    io::{Read, Write},
 };
 
 use anyhow::{bail, Error, Result};
@@ -8,6 +8,16 @@ use crc::{Crc, CRC_32_ISO_HDLC};
 
 use crate::chunk_type::ChunkType;
 
+const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Computes the crc over the chunk type and data without concatenating them
+fn checksum(chunk_type: &[u8], data: &[u8]) -> u32 {
+    let mut digest = CRC.digest();
+    digest.update(chunk_type);
+    digest.update(data);
+    digest.finalize()
+}
+
 #[derive(Clone, Debug)]
 pub struct Chunk {
     chunk_type: ChunkType,
@@ -15,12 +25,36 @@ pub struct Chunk {
 }
 
 impl Chunk {
+    /// Largest data length the PNG spec allows
+    pub const MAX_LENGTH: usize = i32::MAX as usize;
+
+    /// # Panics
+    ///
+    /// If `data` is longer than [`Chunk::MAX_LENGTH`], use [`Chunk::try_new`]
+    /// for data that isn't known to fit.
     pub fn new(chunk_type: ChunkType, data: Vec<u8>) -> Self {
-        Self { chunk_type, data }
+        match Self::try_new(chunk_type, data) {
+            Ok(chunk) => chunk,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    /// Like [`Chunk::new`], but fails for data longer than [`Chunk::MAX_LENGTH`]
+    pub fn try_new(chunk_type: ChunkType, data: Vec<u8>) -> Result<Self> {
+        if data.len() > Self::MAX_LENGTH {
+            bail!(
+                "chunk data is {} bytes long, at most {} bytes are allowed",
+                data.len(),
+                Self::MAX_LENGTH
+            );
+        }
+
+        Ok(Self { chunk_type, data })
     }
 
     pub fn length(&self) -> u32 {
-        self.data.len().try_into().unwrap()
+        // every constructor keeps the data within MAX_LENGTH
+        self.data.len() as u32
     }
 
     pub fn chunk_type(&self) -> &ChunkType {
@@ -32,17 +66,7 @@ impl Chunk {
     }
 
     pub fn crc(&self) -> u32 {
-        let crc: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
-
-        let bytes: Vec<u8> = self
-            .chunk_type
-            .bytes()
-            .iter()
-            .chain(self.data.iter())
-            .copied()
-            .collect();
-
-        crc.checksum(&bytes)
+        checksum(&self.chunk_type.bytes(), &self.data)
     }
 
     pub fn data_as_string(&self) -> Result<String> {
@@ -62,13 +86,19 @@ impl Chunk {
             .cloned()
             .collect()
     }
-}
 
-impl TryFrom<&[u8]> for Chunk {
-    type Error = anyhow::Error;
+    /// Writes the chunk without building an intermediate buffer
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writer.write_all(&self.length().to_be_bytes())?;
+        writer.write_all(&self.chunk_type.bytes())?;
+        writer.write_all(&self.data)?;
+        writer.write_all(&self.crc().to_be_bytes())?;
+
+        Ok(())
+    }
 
-    fn try_from(value: &[u8]) -> Result<Self, self::Error> {
-        let mut reader = BufReader::new(value);
+    /// Reads a single chunk from `reader` and verifies its crc
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
         let mut length: [u8; 4] = [0, 0, 0, 0];
 
         if reader.read_exact(&mut length).is_err() {
@@ -76,6 +106,12 @@ impl TryFrom<&[u8]> for Chunk {
         };
 
         let length = u32::from_be_bytes(length);
+        if usize::try_from(length)? > Self::MAX_LENGTH {
+            bail!(
+                "chunk length {length} exceeds the maximum of {}",
+                Self::MAX_LENGTH
+            )
+        }
 
         let mut chunk_type: [u8; 4] = [0, 0, 0, 0];
         if reader.read_exact(&mut chunk_type).is_err() {
@@ -88,9 +124,11 @@ impl TryFrom<&[u8]> for Chunk {
             bail!("invalid chunk type")
         };
 
-        let mut data = vec![0u8; length.try_into()?];
+        // don't trust the length before the bytes actually arrived
+        let mut data = Vec::new();
+        let read = reader.take(length.into()).read_to_end(&mut data);
 
-        if reader.read_exact(&mut data).is_err() {
+        if read.is_err() || data.len() != usize::try_from(length)? {
             bail!("Failed reading data bytes")
         };
 
@@ -112,6 +150,105 @@ impl TryFrom<&[u8]> for Chunk {
     }
 }
 
+impl TryFrom<&[u8]> for Chunk {
+    type Error = anyhow::Error;
+
+    fn try_from(mut value: &[u8]) -> Result<Self, self::Error> {
+        Self::read_from(&mut value)
+    }
+}
+
+/// A chunk borrowed from a byte slice, e.g. a whole file or a memory map
+///
+/// Parsing only checks that the chunk fits into the slice, the type and crc
+/// are checked once the chunk is converted into an owned [`Chunk`].
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkRef<'a> {
+    offset: usize,
+    bytes: &'a [u8],
+}
+
+impl<'a> ChunkRef<'a> {
+    /// Length, type and crc fields around the data
+    pub const OVERHEAD: usize = 12;
+
+    /// Parses the chunk at the start of `bytes` and returns it with the remaining bytes
+    pub fn parse(bytes: &'a [u8]) -> Result<(Self, &'a [u8])> {
+        Self::parse_at(bytes, 0)
+    }
+
+    /// Like [`ChunkRef::parse`], for a chunk found at `offset` in its file
+    pub fn parse_at(bytes: &'a [u8], offset: usize) -> Result<(Self, &'a [u8])> {
+        let Some(length) = bytes.get(..4) else {
+            bail!("Failed reading data_length bytes")
+        };
+
+        let length = u32::from_be_bytes(length.try_into()?);
+        let total = usize::try_from(length)? + Self::OVERHEAD;
+
+        if bytes.len() < total {
+            bail!("Failed reading chunk bytes")
+        }
+
+        let (bytes, rest) = bytes.split_at(total);
+
+        Ok((Self { offset, bytes }, rest))
+    }
+
+    /// Byte offset of the chunk inside its file
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn length(&self) -> u32 {
+        u32::from_be_bytes(self.bytes[..4].try_into().unwrap())
+    }
+
+    pub fn chunk_type_bytes(&self) -> [u8; 4] {
+        self.bytes[4..8].try_into().unwrap()
+    }
+
+    pub fn chunk_type(&self) -> Result<ChunkType> {
+        ChunkType::try_from(self.chunk_type_bytes())
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        &self.bytes[8..self.bytes.len() - 4]
+    }
+
+    /// The crc as stored in the file
+    pub fn stored_crc(&self) -> u32 {
+        u32::from_be_bytes(self.bytes[self.bytes.len() - 4..].try_into().unwrap())
+    }
+
+    /// The crc computed over the type and data
+    pub fn crc(&self) -> u32 {
+        checksum(&self.bytes[4..8], self.data())
+    }
+
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc() == self.stored_crc()
+    }
+
+    /// The raw bytes of the whole chunk
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Copies the chunk into an owned [`Chunk`], checking its type and crc
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        Chunk::try_from(self.bytes)
+    }
+}
+
+impl TryFrom<ChunkRef<'_>> for Chunk {
+    type Error = anyhow::Error;
+
+    fn try_from(value: ChunkRef<'_>) -> Result<Self, Self::Error> {
+        value.to_chunk()
+    }
+}
+
 impl Display for Chunk {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let data = String::from_utf8(self.data.clone()).unwrap();
@@ -228,6 +365,16 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_length_over_max() {
+        let mut chunk_data = (Chunk::MAX_LENGTH as u32 + 1).to_be_bytes().to_vec();
+        chunk_data.extend(b"RuSt");
+
+        let error = Chunk::try_from(chunk_data.as_ref()).unwrap_err();
+
+        assert!(error.to_string().contains("exceeds the maximum"));
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -248,4 +395,67 @@ mod tests {
 
         let _chunk_string = format!("{chunk}");
     }
+
+    #[test]
+    fn test_chunk_ref() {
+        let chunk = testing_chunk();
+        let bytes = chunk.as_bytes();
+        let (chunk_ref, rest) = ChunkRef::parse(&bytes).unwrap();
+
+        assert!(rest.is_empty());
+        assert_eq!(chunk_ref.length(), 42);
+        assert_eq!(chunk_ref.chunk_type().unwrap(), *chunk.chunk_type());
+        assert_eq!(chunk_ref.data(), chunk.data());
+        assert_eq!(chunk_ref.crc(), 2882656334);
+        assert!(chunk_ref.is_crc_valid());
+        assert_eq!(chunk_ref.as_bytes(), &bytes[..]);
+        assert_eq!(chunk_ref.to_chunk().unwrap().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_chunk_ref_bad_crc() {
+        let mut bytes = testing_chunk().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+
+        let (chunk_ref, _) = ChunkRef::parse(&bytes).unwrap();
+
+        assert!(!chunk_ref.is_crc_valid());
+        assert_ne!(chunk_ref.stored_crc(), chunk_ref.crc());
+        assert!(Chunk::try_from(chunk_ref).is_err());
+    }
+
+    #[test]
+    fn test_chunk_ref_truncated() {
+        let bytes = testing_chunk().as_bytes();
+
+        assert!(ChunkRef::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ChunkRef::parse(&bytes[..3]).is_err());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let chunk = testing_chunk();
+        let mut bytes = Vec::new();
+        chunk.write_to(&mut bytes).unwrap();
+
+        assert_eq!(bytes, chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_read_from_leaves_rest() {
+        let chunk = testing_chunk();
+        let bytes: Vec<u8> = chunk
+            .as_bytes()
+            .iter()
+            .chain(chunk.as_bytes().iter())
+            .copied()
+            .collect();
+
+        let mut reader = &bytes[..];
+        Chunk::read_from(&mut reader).unwrap();
+        assert_eq!(reader.len(), chunk.as_bytes().len());
+        Chunk::read_from(&mut reader).unwrap();
+        assert!(reader.is_empty());
+    }
 }
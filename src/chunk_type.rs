@@ -15,6 +15,10 @@ impl TryFrom<[u8; 4]> for ChunkType {
     type Error = anyhow::Error;
 
     fn try_from(value: [u8; 4]) -> Result<Self, Self::Error> {
+        if !value.iter().all(u8::is_ascii_alphabetic) {
+            bail!("chunk type can only contain ASCII letters");
+        }
+
         let code = String::from_utf8(value.to_vec())?;
         let code: Vec<char> = code.chars().collect();
 
@@ -105,6 +109,12 @@ mod tests {
         assert_eq!(expected, actual.bytes());
     }
 
+    #[test]
+    pub fn test_chunk_type_from_invalid_bytes() {
+        assert!(ChunkType::try_from([82, 117, 49, 116]).is_err());
+        assert!(ChunkType::try_from([0xc3, 0xa4, 83, 116]).is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_from_str() {
         let expected = ChunkType::try_from([82, 117, 83, 116]).unwrap();
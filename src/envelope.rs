@@ -0,0 +1,663 @@
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use flate2::Compression;
+use serde::Serialize;
+
+use crate::{fragment, zlib};
+
+/// Marks chunk data as an envelope rather than a plain message
+pub const MAGIC: [u8; 4] = *b"PNGm";
+
+const VERSION: u8 = 1;
+
+const FLAG_ENCRYPTED: u8 = 0b0000_0001;
+const FLAG_FILE: u8 = 0b0000_0010;
+const FLAG_COMPRESSED: u8 = 0b0000_0100;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const CHECK_LENGTH: usize = 16;
+const KEY_LENGTH: usize = 32;
+
+/// Largest body a compressed envelope may inflate to
+const MAX_BODY_LENGTH: usize = 256 * 1024 * 1024;
+
+/// Errors that can happen while opening an envelope
+#[derive(Debug, PartialEq, Eq)]
+pub enum EnvelopeError {
+    Truncated,
+    UnsupportedVersion(u8),
+    PassphraseRequired,
+    WrongPassphrase,
+    Tampered,
+    /// Compressed body or key derivation settings that can't be right
+    Corrupted,
+}
+
+impl Display for EnvelopeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvelopeError::Truncated => write!(f, "envelope is truncated"),
+            EnvelopeError::UnsupportedVersion(v) => write!(f, "unsupported envelope version {v}"),
+            EnvelopeError::PassphraseRequired => {
+                write!(f, "message is encrypted, a passphrase is required")
+            }
+            EnvelopeError::WrongPassphrase => write!(f, "wrong passphrase"),
+            EnvelopeError::Tampered => write!(f, "message has been tampered with"),
+            EnvelopeError::Corrupted => write!(f, "envelope is corrupted"),
+        }
+    }
+}
+
+impl std::error::Error for EnvelopeError {}
+
+/// Key derivation settings, stored in the envelope so it can always be reopened
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    const LENGTH: usize = 12;
+
+    /// Upper bounds for settings read from an envelope, so a crafted one can't
+    /// make opening it take forever or exhaust memory
+    const MAX_M_COST: u32 = 4 * Params::DEFAULT_M_COST;
+    const MAX_T_COST: u32 = 4 * Params::DEFAULT_T_COST;
+    const MAX_P_COST: u32 = 16;
+
+    fn as_bytes(&self) -> [u8; Self::LENGTH] {
+        let mut bytes = [0u8; Self::LENGTH];
+        bytes[0..4].copy_from_slice(&self.m_cost.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.t_cost.to_be_bytes());
+        bytes[8..12].copy_from_slice(&self.p_cost.to_be_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, EnvelopeError> {
+        let word = |i: usize| u32::from_be_bytes(bytes[i..i + 4].try_into().unwrap());
+
+        let params = Self {
+            m_cost: word(0),
+            t_cost: word(4),
+            p_cost: word(8),
+        };
+
+        if params.m_cost > Self::MAX_M_COST
+            || params.t_cost > Self::MAX_T_COST
+            || params.p_cost > Self::MAX_P_COST
+        {
+            return Err(EnvelopeError::Corrupted);
+        }
+
+        Ok(params)
+    }
+
+    /// Derives the cipher key and the passphrase check value
+    fn derive(
+        &self,
+        passphrase: &str,
+        salt: &[u8],
+    ) -> Result<([u8; KEY_LENGTH], [u8; CHECK_LENGTH])> {
+        let params = Params::new(
+            self.m_cost,
+            self.t_cost,
+            self.p_cost,
+            Some(KEY_LENGTH + CHECK_LENGTH),
+        )?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+        let mut output = [0u8; KEY_LENGTH + CHECK_LENGTH];
+        argon2.hash_password_into(passphrase.as_bytes(), salt, &mut output)?;
+
+        let mut key = [0u8; KEY_LENGTH];
+        let mut check = [0u8; CHECK_LENGTH];
+        key.copy_from_slice(&output[..KEY_LENGTH]);
+        check.copy_from_slice(&output[KEY_LENGTH..]);
+
+        Ok((key, check))
+    }
+}
+
+/// Name, size and type of an embedded file
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FileInfo {
+    pub name: String,
+    pub size: u64,
+    /// MIME type guessed from the file name, i.e. `image/png`
+    pub mime: String,
+}
+
+impl FileInfo {
+    /// Describes `content` named `name`, guessing the MIME type from its extension
+    pub fn new(name: &str, content: &[u8]) -> Self {
+        Self {
+            name: name.to_string(),
+            size: content.len() as u64,
+            mime: guess_mime(name).to_string(),
+        }
+    }
+
+    /// Layout: `name length (u16) | name | size (u64) | mime length (u8) | mime`
+    fn as_bytes(&self) -> Result<Vec<u8>> {
+        let name_length = u16::try_from(self.name.len())?;
+        let mime_length = u8::try_from(self.mime.len())?;
+
+        let mut bytes = name_length.to_be_bytes().to_vec();
+        bytes.extend(self.name.as_bytes());
+        bytes.extend(self.size.to_be_bytes());
+        bytes.push(mime_length);
+        bytes.extend(self.mime.as_bytes());
+
+        Ok(bytes)
+    }
+
+    /// Parses the header in front of the file content, returning both
+    fn parse(bytes: &[u8]) -> Result<(Self, &[u8])> {
+        let (name_length, rest) = take(bytes, 2)?;
+        let (name, rest) = take(rest, u16::from_be_bytes(name_length.try_into()?).into())?;
+        let (size, rest) = take(rest, 8)?;
+        let (mime_length, rest) = take(rest, 1)?;
+        let (mime, content) = take(rest, mime_length[0].into())?;
+
+        let info = Self {
+            name: String::from_utf8(name.to_vec())?,
+            size: u64::from_be_bytes(size.try_into()?),
+            mime: String::from_utf8(mime.to_vec())?,
+        };
+
+        if info.size != content.len() as u64 {
+            bail!(EnvelopeError::Truncated);
+        }
+
+        Ok((info, content))
+    }
+}
+
+impl Display for FileInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<file {}, {} bytes, {}>",
+            self.name, self.size, self.mime
+        )
+    }
+}
+
+fn take(bytes: &[u8], n: usize) -> Result<(&[u8], &[u8]), EnvelopeError> {
+    match bytes.len() >= n {
+        true => Ok(bytes.split_at(n)),
+        false => Err(EnvelopeError::Truncated),
+    }
+}
+
+fn guess_mime(name: &str) -> &'static str {
+    let extension = name.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("txt" | "log" | "md") => "text/plain",
+        Some("html" | "htm") => "text/html",
+        Some("csv") => "text/csv",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("zip") => "application/zip",
+        Some("gz") => "application/gzip",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("mp3") => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
+
+/// What gets hidden: a plain message or the content of a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    pub file: Option<FileInfo>,
+    pub content: Vec<u8>,
+}
+
+impl Message {
+    pub fn text(text: &str) -> Self {
+        Self {
+            file: None,
+            content: text.as_bytes().to_vec(),
+        }
+    }
+
+    pub fn file(name: &str, content: Vec<u8>) -> Self {
+        Self {
+            file: Some(FileInfo::new(name, &content)),
+            content,
+        }
+    }
+}
+
+/// How a message is sealed
+#[derive(Clone, Debug, Default)]
+pub struct SealOptions<'a> {
+    /// Encrypt with a key derived from this passphrase
+    pub passphrase: Option<&'a str>,
+    pub kdf_params: KdfParams,
+    /// Deflate the body before encrypting it, skipped if that doesn't make it smaller
+    pub compress: bool,
+}
+
+/// Returns whether `data` starts with the envelope magic
+pub fn is_envelope(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Returns whether `data` is an envelope holding an encrypted message
+pub fn is_encrypted(data: &[u8]) -> bool {
+    is_envelope(data)
+        && data
+            .get(MAGIC.len() + 1)
+            .is_some_and(|f| f & FLAG_ENCRYPTED != 0)
+}
+
+/// Builds the chunk data for `message`
+///
+/// Plain text messages are stored as is unless they start with either magic,
+/// everything else goes into an envelope:
+/// `MAGIC | version | flags | [kdf params | salt | nonce | check] | body`.
+/// The body is the file header (if any) followed by the content, compressed if
+/// that was asked for and encrypted if a passphrase is given. Everything in
+/// front of the ciphertext is authenticated as associated data.
+pub fn seal(message: &Message, options: &SealOptions) -> Result<Vec<u8>> {
+    let mut flags = 0;
+    let mut body = vec![];
+
+    if let Some(file) = &message.file {
+        flags |= FLAG_FILE;
+        body.extend(file.as_bytes()?);
+    }
+    body.extend_from_slice(&message.content);
+
+    if options.compress {
+        let compressed = zlib::compress(&body, Compression::best())?;
+        if compressed.len() < body.len() {
+            flags |= FLAG_COMPRESSED;
+            body = compressed;
+        }
+    }
+
+    if flags == 0
+        && options.passphrase.is_none()
+        && !is_envelope(&body)
+        && !fragment::is_fragment(&body)
+    {
+        return Ok(body);
+    }
+
+    let Some(passphrase) = options.passphrase else {
+        let mut data = vec![];
+        data.extend(MAGIC);
+        data.extend([VERSION, flags]);
+        data.extend(body);
+        return Ok(data);
+    };
+
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    getrandom::getrandom(&mut salt)?;
+    getrandom::getrandom(&mut nonce)?;
+
+    let params = options.kdf_params;
+    let (key, check) = params.derive(passphrase, &salt)?;
+
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+    header.push(flags | FLAG_ENCRYPTED);
+    header.extend_from_slice(&params.as_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+    header.extend_from_slice(&check);
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    let ciphertext = match cipher.encrypt(
+        Nonce::from_slice(&nonce),
+        Payload {
+            msg: &body,
+            aad: &header,
+        },
+    ) {
+        Ok(c) => c,
+        Err(_) => bail!("Failed encrypting message"),
+    };
+
+    header.extend(ciphertext);
+
+    Ok(header)
+}
+
+/// Returns the message stored in `data`
+///
+/// Data without the envelope magic is a plain message.
+pub fn open(data: &[u8], passphrase: Option<&str>) -> Result<Message> {
+    if !is_envelope(data) {
+        return Ok(Message {
+            file: None,
+            content: data.to_vec(),
+        });
+    }
+
+    let (version, flags) = match data.get(MAGIC.len()..MAGIC.len() + 2) {
+        Some(&[version, flags]) => (version, flags),
+        _ => bail!(EnvelopeError::Truncated),
+    };
+
+    if version != VERSION {
+        bail!(EnvelopeError::UnsupportedVersion(version));
+    }
+
+    let body = &data[MAGIC.len() + 2..];
+
+    let body = match flags & FLAG_ENCRYPTED != 0 {
+        true => decrypt(data, body, passphrase)?,
+        false => body.to_vec(),
+    };
+
+    let body = match flags & FLAG_COMPRESSED != 0 {
+        true => zlib::decompress(&body, MAX_BODY_LENGTH).map_err(|_| EnvelopeError::Corrupted)?,
+        false => body,
+    };
+
+    if flags & FLAG_FILE == 0 {
+        return Ok(Message {
+            file: None,
+            content: body,
+        });
+    }
+
+    let (file, content) = FileInfo::parse(&body)?;
+
+    Ok(Message {
+        file: Some(file),
+        content: content.to_vec(),
+    })
+}
+
+fn decrypt(data: &[u8], body: &[u8], passphrase: Option<&str>) -> Result<Vec<u8>> {
+    let Some(passphrase) = passphrase else {
+        bail!(EnvelopeError::PassphraseRequired);
+    };
+
+    let header_length = KdfParams::LENGTH + SALT_LENGTH + NONCE_LENGTH + CHECK_LENGTH;
+    if body.len() < header_length {
+        bail!(EnvelopeError::Truncated);
+    }
+
+    let (params, rest) = body.split_at(KdfParams::LENGTH);
+    let (salt, rest) = rest.split_at(SALT_LENGTH);
+    let (nonce, rest) = rest.split_at(NONCE_LENGTH);
+    let (stored_check, ciphertext) = rest.split_at(CHECK_LENGTH);
+
+    let params = KdfParams::from_bytes(params)?;
+    let (key, check) = params.derive(passphrase, salt)?;
+
+    if check != stored_check {
+        bail!(EnvelopeError::WrongPassphrase);
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    match cipher.decrypt(
+        Nonce::from_slice(nonce),
+        Payload {
+            msg: ciphertext,
+            aad: &data[..data.len() - ciphertext.len()],
+        },
+    ) {
+        Ok(message) => Ok(message),
+        Err(_) => bail!(EnvelopeError::Tampered),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Keeps the tests fast, the defaults are tuned for real passphrases
+    const TEST_PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    fn seal_text(text: &str, passphrase: &str) -> Vec<u8> {
+        let options = SealOptions {
+            passphrase: Some(passphrase),
+            kdf_params: TEST_PARAMS,
+            ..Default::default()
+        };
+
+        seal(&Message::text(text), &options).unwrap()
+    }
+
+    fn error_of(result: Result<Message>) -> EnvelopeError {
+        match result.unwrap_err().downcast::<EnvelopeError>() {
+            Ok(e) => e,
+            Err(e) => panic!("unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let sealed = seal_text("secret", "hunter2");
+
+        assert!(is_envelope(&sealed));
+        assert!(is_encrypted(&sealed));
+        assert_eq!(
+            open(&sealed, Some("hunter2")).unwrap(),
+            Message::text("secret")
+        );
+    }
+
+    #[test]
+    fn test_open_plain_data() {
+        assert_eq!(
+            open(b"plain message", None).unwrap(),
+            Message::text("plain message")
+        );
+        assert!(!is_encrypted(b"plain message"));
+
+        let sealed = seal(&Message::text("plain message"), &SealOptions::default()).unwrap();
+        assert_eq!(sealed, b"plain message");
+    }
+
+    #[test]
+    fn test_open_without_passphrase() {
+        let sealed = seal_text("secret", "hunter2");
+        assert_eq!(
+            error_of(open(&sealed, None)),
+            EnvelopeError::PassphraseRequired
+        );
+    }
+
+    #[test]
+    fn test_open_wrong_passphrase() {
+        let sealed = seal_text("secret", "hunter2");
+        assert_eq!(
+            error_of(open(&sealed, Some("hunter3"))),
+            EnvelopeError::WrongPassphrase
+        );
+    }
+
+    #[test]
+    fn test_open_tampered_ciphertext() {
+        let mut sealed = seal_text("secret", "hunter2");
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+
+        assert_eq!(
+            error_of(open(&sealed, Some("hunter2"))),
+            EnvelopeError::Tampered
+        );
+    }
+
+    #[test]
+    fn test_open_tampered_header() {
+        let mut sealed = seal_text("secret", "hunter2");
+        // first nonce byte, covered by the associated data
+        sealed[MAGIC.len() + 2 + KdfParams::LENGTH + SALT_LENGTH] ^= 1;
+
+        assert_eq!(
+            error_of(open(&sealed, Some("hunter2"))),
+            EnvelopeError::Tampered
+        );
+    }
+
+    #[test]
+    fn test_open_excessive_kdf_params() {
+        // m_cost, t_cost and p_cost, in that order
+        for (offset, value) in [(0, u32::MAX), (4, 1_000_000), (8, 17)] {
+            let mut sealed = seal_text("secret", "hunter2");
+            let start = MAGIC.len() + 2 + offset;
+            sealed[start..start + 4].copy_from_slice(&value.to_be_bytes());
+
+            assert_eq!(
+                error_of(open(&sealed, Some("hunter2"))),
+                EnvelopeError::Corrupted
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_truncated() {
+        let sealed = seal_text("secret", "hunter2");
+        assert_eq!(
+            error_of(open(&sealed[..20], Some("hunter2"))),
+            EnvelopeError::Truncated
+        );
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let message = Message::file("notes.txt", b"file content".to_vec());
+        let sealed = seal(&message, &SealOptions::default()).unwrap();
+
+        assert!(is_envelope(&sealed));
+        assert!(!is_encrypted(&sealed));
+
+        let opened = open(&sealed, None).unwrap();
+        assert_eq!(opened, message);
+
+        let file = opened.file.unwrap();
+        assert_eq!(file.name, "notes.txt");
+        assert_eq!(file.size, 12);
+        assert_eq!(file.mime, "text/plain");
+    }
+
+    #[test]
+    fn test_encrypted_file_round_trip() {
+        let message = Message::file("image.PNG", vec![0, 159, 146, 150]);
+        let options = SealOptions {
+            passphrase: Some("hunter2"),
+            kdf_params: TEST_PARAMS,
+            ..Default::default()
+        };
+        let sealed = seal(&message, &options).unwrap();
+
+        let opened = open(&sealed, Some("hunter2")).unwrap();
+        assert_eq!(opened, message);
+        assert_eq!(opened.file.unwrap().mime, "image/png");
+    }
+
+    #[test]
+    fn test_file_size_mismatch() {
+        let message = Message::file("notes.txt", b"file content".to_vec());
+        let sealed = seal(&message, &SealOptions::default()).unwrap();
+
+        assert_eq!(
+            error_of(open(&sealed[..sealed.len() - 1], None)),
+            EnvelopeError::Truncated
+        );
+    }
+
+    #[test]
+    fn test_text_looking_like_envelope() {
+        let message = Message::text("PNGm is our magic");
+        let sealed = seal(&message, &SealOptions::default()).unwrap();
+
+        assert_eq!(open(&sealed, None).unwrap(), message);
+    }
+
+    #[test]
+    fn test_text_looking_like_fragment() {
+        let message = Message::text("PNGf is my favorite prefix");
+        let sealed = seal(&message, &SealOptions::default()).unwrap();
+
+        assert!(!fragment::is_fragment(&sealed));
+        assert_eq!(open(&sealed, None).unwrap(), message);
+    }
+
+    #[test]
+    fn test_seal_is_randomized() {
+        let a = seal_text("secret", "hunter2");
+        let b = seal_text("secret", "hunter2");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let message = Message::text(&"all work and no play ".repeat(100));
+        let options = SealOptions {
+            compress: true,
+            ..Default::default()
+        };
+        let sealed = seal(&message, &options).unwrap();
+
+        assert!(is_envelope(&sealed));
+        assert!(sealed.len() < message.content.len() / 10);
+        assert_eq!(open(&sealed, None).unwrap(), message);
+    }
+
+    #[test]
+    fn test_compressed_encrypted_file_round_trip() {
+        let message = Message::file("log.txt", b"GET / 200\n".repeat(50));
+        let options = SealOptions {
+            passphrase: Some("hunter2"),
+            kdf_params: TEST_PARAMS,
+            compress: true,
+        };
+        let sealed = seal(&message, &options).unwrap();
+
+        assert!(sealed.len() < message.content.len());
+        assert_eq!(open(&sealed, Some("hunter2")).unwrap(), message);
+    }
+
+    #[test]
+    fn test_compression_skipped_if_larger() {
+        let message = Message::text("hi");
+        let options = SealOptions {
+            compress: true,
+            ..Default::default()
+        };
+
+        assert_eq!(seal(&message, &options).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn test_open_corrupted_compression() {
+        let data = [&MAGIC[..], &[VERSION, FLAG_COMPRESSED], b"not zlib"].concat();
+        assert_eq!(error_of(open(&data, None)), EnvelopeError::Corrupted);
+    }
+}
@@ -0,0 +1,1102 @@
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png};
+
+pub const CHUNK_TYPE: &str = "eXIf";
+
+/// IFDs nested deeper than this are rejected
+const MAX_DEPTH: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// `II`
+    Little,
+    /// `MM`
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+/// Tags of the GPS and interoperability IFDs are numbered on their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    /// IFD0, IFD1 and the Exif IFD
+    Tiff,
+    Gps,
+    Interop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IfdKind {
+    /// IFD0, describing the main image
+    Image,
+    /// IFD1, describing the thumbnail
+    Thumbnail,
+    Exif,
+    Gps,
+    Interop,
+}
+
+impl IfdKind {
+    pub fn namespace(&self) -> Namespace {
+        match self {
+            IfdKind::Image | IfdKind::Thumbnail | IfdKind::Exif => Namespace::Tiff,
+            IfdKind::Gps => Namespace::Gps,
+            IfdKind::Interop => Namespace::Interop,
+        }
+    }
+}
+
+impl Display for IfdKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            IfdKind::Image => "IFD0",
+            IfdKind::Thumbnail => "IFD1 (thumbnail)",
+            IfdKind::Exif => "Exif",
+            IfdKind::Gps => "GPS",
+            IfdKind::Interop => "Interoperability",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// A tag number in the namespace it belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Tag {
+    pub namespace: Namespace,
+    pub number: u16,
+}
+
+impl Tag {
+    pub const MAKE: Tag = Tag::tiff(0x010f);
+    pub const MODEL: Tag = Tag::tiff(0x0110);
+    pub const ORIENTATION: Tag = Tag::tiff(0x0112);
+    pub const DATE_TIME: Tag = Tag::tiff(0x0132);
+    pub const THUMBNAIL_OFFSET: Tag = Tag::tiff(0x0201);
+    pub const THUMBNAIL_LENGTH: Tag = Tag::tiff(0x0202);
+    pub const EXIF_IFD: Tag = Tag::tiff(0x8769);
+    pub const GPS_IFD: Tag = Tag::tiff(0x8825);
+    pub const DATE_TIME_ORIGINAL: Tag = Tag::tiff(0x9003);
+    pub const DATE_TIME_DIGITIZED: Tag = Tag::tiff(0x9004);
+    pub const INTEROP_IFD: Tag = Tag::tiff(0xa005);
+    pub const GPS_LATITUDE_REF: Tag = Tag::gps(0x0001);
+    pub const GPS_LATITUDE: Tag = Tag::gps(0x0002);
+    pub const GPS_LONGITUDE_REF: Tag = Tag::gps(0x0003);
+    pub const GPS_LONGITUDE: Tag = Tag::gps(0x0004);
+
+    pub const fn tiff(number: u16) -> Self {
+        Self {
+            namespace: Namespace::Tiff,
+            number,
+        }
+    }
+
+    pub const fn gps(number: u16) -> Self {
+        Self {
+            namespace: Namespace::Gps,
+            number,
+        }
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        TAG_NAMES
+            .iter()
+            .find(|(tag, _)| tag == self)
+            .map(|(_, name)| *name)
+    }
+
+    /// The kind of IFD this tag points to, if it is a pointer
+    fn sub_ifd(&self) -> Option<IfdKind> {
+        match *self {
+            Tag::EXIF_IFD => Some(IfdKind::Exif),
+            Tag::GPS_IFD => Some(IfdKind::Gps),
+            Tag::INTEROP_IFD => Some(IfdKind::Interop),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    /// Parses a tag name like `Orientation`, or a number like `0x0112` with an
+    /// optional `gps:` or `interop:` prefix for the GPS and interoperability IFDs
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((tag, _)) = TAG_NAMES
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(s))
+        {
+            return Ok(*tag);
+        }
+
+        let (namespace, number) = match s.split_once(':') {
+            Some(("gps", number)) => (Namespace::Gps, number),
+            Some(("interop", number)) => (Namespace::Interop, number),
+            Some(_) => bail!("unknown tag namespace in `{s}`, expected `gps:` or `interop:`"),
+            None => (Namespace::Tiff, s),
+        };
+
+        let number = match number.strip_prefix("0x") {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => number.parse(),
+        }
+        .map_err(|_| anyhow!("unknown tag `{s}`, expected a tag name or number"))?;
+
+        Ok(Self { namespace, number })
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = self.name() {
+            return write!(f, "{name}");
+        }
+
+        match self.namespace {
+            Namespace::Tiff => write!(f, "0x{:04x}", self.number),
+            Namespace::Gps => write!(f, "gps:0x{:04x}", self.number),
+            Namespace::Interop => write!(f, "interop:0x{:04x}", self.number),
+        }
+    }
+}
+
+const TAG_NAMES: [(Tag, &str); 62] = [
+    (Tag::tiff(0x0100), "ImageWidth"),
+    (Tag::tiff(0x0101), "ImageLength"),
+    (Tag::tiff(0x0103), "Compression"),
+    (Tag::tiff(0x010e), "ImageDescription"),
+    (Tag::MAKE, "Make"),
+    (Tag::MODEL, "Model"),
+    (Tag::ORIENTATION, "Orientation"),
+    (Tag::tiff(0x011a), "XResolution"),
+    (Tag::tiff(0x011b), "YResolution"),
+    (Tag::tiff(0x0128), "ResolutionUnit"),
+    (Tag::tiff(0x0131), "Software"),
+    (Tag::DATE_TIME, "DateTime"),
+    (Tag::tiff(0x013b), "Artist"),
+    (Tag::THUMBNAIL_OFFSET, "JPEGInterchangeFormat"),
+    (Tag::THUMBNAIL_LENGTH, "JPEGInterchangeFormatLength"),
+    (Tag::tiff(0x0213), "YCbCrPositioning"),
+    (Tag::tiff(0x8298), "Copyright"),
+    (Tag::tiff(0x829a), "ExposureTime"),
+    (Tag::tiff(0x829d), "FNumber"),
+    (Tag::EXIF_IFD, "ExifIFD"),
+    (Tag::tiff(0x8822), "ExposureProgram"),
+    (Tag::GPS_IFD, "GPSInfo"),
+    (Tag::tiff(0x8827), "ISOSpeedRatings"),
+    (Tag::tiff(0x9000), "ExifVersion"),
+    (Tag::DATE_TIME_ORIGINAL, "DateTimeOriginal"),
+    (Tag::DATE_TIME_DIGITIZED, "DateTimeDigitized"),
+    (Tag::tiff(0x9010), "OffsetTime"),
+    (Tag::tiff(0x9011), "OffsetTimeOriginal"),
+    (Tag::tiff(0x9012), "OffsetTimeDigitized"),
+    (Tag::tiff(0x9201), "ShutterSpeedValue"),
+    (Tag::tiff(0x9202), "ApertureValue"),
+    (Tag::tiff(0x9204), "ExposureBiasValue"),
+    (Tag::tiff(0x9207), "MeteringMode"),
+    (Tag::tiff(0x9209), "Flash"),
+    (Tag::tiff(0x920a), "FocalLength"),
+    (Tag::tiff(0x927c), "MakerNote"),
+    (Tag::tiff(0x9286), "UserComment"),
+    (Tag::tiff(0xa000), "FlashpixVersion"),
+    (Tag::tiff(0xa001), "ColorSpace"),
+    (Tag::tiff(0xa002), "PixelXDimension"),
+    (Tag::tiff(0xa003), "PixelYDimension"),
+    (Tag::INTEROP_IFD, "InteropIFD"),
+    (Tag::tiff(0xa402), "ExposureMode"),
+    (Tag::tiff(0xa403), "WhiteBalance"),
+    (Tag::tiff(0xa405), "FocalLengthIn35mmFilm"),
+    (Tag::tiff(0xa420), "ImageUniqueID"),
+    (Tag::tiff(0xa430), "CameraOwnerName"),
+    (Tag::tiff(0xa431), "BodySerialNumber"),
+    (Tag::tiff(0xa434), "LensModel"),
+    (Tag::gps(0x0000), "GPSVersionID"),
+    (Tag::GPS_LATITUDE_REF, "GPSLatitudeRef"),
+    (Tag::GPS_LATITUDE, "GPSLatitude"),
+    (Tag::GPS_LONGITUDE_REF, "GPSLongitudeRef"),
+    (Tag::GPS_LONGITUDE, "GPSLongitude"),
+    (Tag::gps(0x0005), "GPSAltitudeRef"),
+    (Tag::gps(0x0006), "GPSAltitude"),
+    (Tag::gps(0x0007), "GPSTimeStamp"),
+    (Tag::gps(0x0011), "GPSImgDirection"),
+    (Tag::gps(0x0012), "GPSMapDatum"),
+    (Tag::gps(0x001d), "GPSDateStamp"),
+    (
+        Tag {
+            namespace: Namespace::Interop,
+            number: 0x0001,
+        },
+        "InteroperabilityIndex",
+    ),
+    (
+        Tag {
+            namespace: Namespace::Interop,
+            number: 0x0002,
+        },
+        "InteroperabilityVersion",
+    ),
+];
+
+/// Size in bytes of a single value of a TIFF field type
+fn type_size(field_type: u16) -> Option<usize> {
+    match field_type {
+        // byte, ascii, signed byte, undefined
+        1 | 2 | 6 | 7 => Some(1),
+        // short, signed short
+        3 | 8 => Some(2),
+        // long, signed long, float, ifd
+        4 | 9 | 11 | 13 => Some(4),
+        // rational, signed rational, double
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+/// A decoded field value
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Ascii(String),
+    Bytes(Vec<u8>),
+    Unsigned(Vec<u32>),
+    Signed(Vec<i32>),
+    Rational(Vec<(u32, u32)>),
+    SignedRational(Vec<(i32, i32)>),
+    Float(Vec<f64>),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn list<T>(
+            f: &mut std::fmt::Formatter<'_>,
+            values: &[T],
+            show: impl Fn(&T) -> String,
+        ) -> std::fmt::Result {
+            let shown: Vec<String> = values.iter().take(16).map(show).collect();
+            write!(f, "{}", shown.join(", "))?;
+            if values.len() > 16 {
+                write!(f, ", ... ({} values)", values.len())?;
+            }
+            Ok(())
+        }
+
+        match self {
+            Value::Ascii(text) => write!(f, "{text}"),
+            Value::Bytes(bytes)
+                if !bytes.is_empty() && bytes.iter().all(|b| (0x20..0x7f).contains(b)) =>
+            {
+                write!(f, "\"{}\"", String::from_utf8_lossy(bytes))
+            }
+            Value::Bytes(bytes) if bytes.len() > 16 => write!(f, "{} bytes", bytes.len()),
+            Value::Bytes(bytes) => list(f, bytes, |b| format!("{b:02x}")),
+            Value::Unsigned(values) => list(f, values, u32::to_string),
+            Value::Signed(values) => list(f, values, i32::to_string),
+            Value::Rational(values) => list(f, values, |(n, d)| format!("{n}/{d}")),
+            Value::SignedRational(values) => list(f, values, |(n, d)| format!("{n}/{d}")),
+            Value::Float(values) => list(f, values, f64::to_string),
+        }
+    }
+}
+
+/// A field of an IFD
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub tag: Tag,
+    pub field_type: u16,
+    pub count: u32,
+    /// The value in the byte order of the Exif data, empty for IFD pointers
+    pub data: Vec<u8>,
+    /// The IFD this entry points to
+    pub ifd: Option<Ifd>,
+}
+
+impl Entry {
+    pub fn value(&self, byte_order: ByteOrder) -> Value {
+        let data = &self.data;
+        let words = |size: usize| data.chunks_exact(size);
+
+        match self.field_type {
+            2 => Value::Ascii(
+                String::from_utf8_lossy(data)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            ),
+            3 => Value::Unsigned(words(2).map(|w| byte_order.u16(w).into()).collect()),
+            4 | 13 => Value::Unsigned(words(4).map(|w| byte_order.u32(w)).collect()),
+            5 => Value::Rational(
+                words(8)
+                    .map(|w| (byte_order.u32(w), byte_order.u32(&w[4..])))
+                    .collect(),
+            ),
+            6 => Value::Signed(data.iter().map(|&b| (b as i8).into()).collect()),
+            8 => Value::Signed(
+                words(2)
+                    .map(|w| (byte_order.u16(w) as i16).into())
+                    .collect(),
+            ),
+            9 => Value::Signed(words(4).map(|w| byte_order.u32(w) as i32).collect()),
+            10 => Value::SignedRational(
+                words(8)
+                    .map(|w| (byte_order.u32(w) as i32, byte_order.u32(&w[4..]) as i32))
+                    .collect(),
+            ),
+            11 => Value::Float(
+                words(4)
+                    .map(|w| f32::from_bits(byte_order.u32(w)).into())
+                    .collect(),
+            ),
+            12 => Value::Float(
+                words(8)
+                    .map(|w| {
+                        let high = u64::from(byte_order.u32(w));
+                        let low = u64::from(byte_order.u32(&w[4..]));
+                        let bits = match byte_order {
+                            ByteOrder::Big => high << 32 | low,
+                            ByteOrder::Little => low << 32 | high,
+                        };
+                        f64::from_bits(bits)
+                    })
+                    .collect(),
+            ),
+            _ => Value::Bytes(data.clone()),
+        }
+    }
+}
+
+/// An image file directory, a list of fields
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ifd {
+    pub kind: IfdKind,
+    pub entries: Vec<Entry>,
+}
+
+impl Ifd {
+    fn find(&self, tag: Tag) -> Option<&Entry> {
+        self.entries.iter().find_map(|entry| match &entry.ifd {
+            _ if entry.tag == tag => Some(entry),
+            Some(ifd) => ifd.find(tag),
+            None => None,
+        })
+    }
+
+    fn remove(&mut self, tag: Tag) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|entry| entry.tag != tag);
+        let removed = before - self.entries.len();
+
+        removed
+            + self
+                .entries
+                .iter_mut()
+                .filter_map(|entry| entry.ifd.as_mut())
+                .map(|ifd| ifd.remove(tag))
+                .sum::<usize>()
+    }
+
+    fn fmt_with(&self, f: &mut std::fmt::Formatter<'_>, byte_order: ByteOrder) -> std::fmt::Result {
+        write!(f, "{}:", self.kind)?;
+        for entry in self.entries.iter().filter(|e| e.ifd.is_none()) {
+            write!(f, "\n  {}: {}", entry.tag, entry.value(byte_order))?;
+        }
+
+        for ifd in self.entries.iter().filter_map(|e| e.ifd.as_ref()) {
+            writeln!(f)?;
+            ifd.fmt_with(f, byte_order)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Exif data, a TIFF header followed by a chain of IFDs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Exif {
+    pub byte_order: ByteOrder,
+    /// IFD0 and, if there is a thumbnail, IFD1
+    pub ifds: Vec<Ifd>,
+    /// The JPEG thumbnail IFD1 points to
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+impl Exif {
+    /// The first entry of `tag`, looking into nested IFDs as well
+    pub fn get(&self, tag: Tag) -> Option<&Entry> {
+        self.ifds.iter().find_map(|ifd| ifd.find(tag))
+    }
+
+    pub fn value(&self, tag: Tag) -> Option<Value> {
+        self.get(tag).map(|entry| entry.value(self.byte_order))
+    }
+
+    /// Removes every entry of `tag`, pointers take their IFD with them
+    ///
+    /// Returns the number of entries removed, not counting those in removed IFDs.
+    pub fn remove_tag(&mut self, tag: Tag) -> usize {
+        let removed = self.ifds.iter_mut().map(|ifd| ifd.remove(tag)).sum();
+
+        if self.get(Tag::THUMBNAIL_OFFSET).is_none() {
+            self.thumbnail = None;
+        }
+
+        removed
+    }
+
+    /// Removes the GPS IFD, returns whether there was one
+    pub fn strip_gps(&mut self) -> bool {
+        self.remove_tag(Tag::GPS_IFD) > 0
+    }
+
+    /// `Make` and `Model`
+    pub fn camera(&self) -> Option<String> {
+        let parts: Vec<String> = [Tag::MAKE, Tag::MODEL]
+            .into_iter()
+            .filter_map(|tag| self.value(tag))
+            .map(|value| value.to_string().trim().to_string())
+            .filter(|part| !part.is_empty())
+            .collect();
+
+        (!parts.is_empty()).then(|| parts.join(" "))
+    }
+
+    pub fn orientation(&self) -> Option<u16> {
+        match self.value(Tag::ORIENTATION)? {
+            Value::Unsigned(values) => values.first().and_then(|&v| v.try_into().ok()),
+            _ => None,
+        }
+    }
+
+    /// Latitude and longitude in degrees, negative for south and west
+    pub fn gps_position(&self) -> Option<(f64, f64)> {
+        let coordinate = |tag: Tag, reference: Tag, negative: &str| {
+            let Value::Rational(parts) = self.value(tag)? else {
+                return None;
+            };
+
+            let degrees = parts
+                .iter()
+                .zip([1.0, 60.0, 3600.0])
+                .map(|(&(n, d), scale)| match d {
+                    0 => 0.0,
+                    d => f64::from(n) / f64::from(d) / scale,
+                })
+                .sum::<f64>();
+
+            match self.value(reference) {
+                Some(Value::Ascii(r)) if r == negative => Some(-degrees),
+                _ => Some(degrees),
+            }
+        };
+
+        Some((
+            coordinate(Tag::GPS_LATITUDE, Tag::GPS_LATITUDE_REF, "S")?,
+            coordinate(Tag::GPS_LONGITUDE, Tag::GPS_LONGITUDE_REF, "W")?,
+        ))
+    }
+
+    /// Camera, orientation, timestamps and position, for those present
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let mut summary = vec![];
+
+        if let Some(camera) = self.camera() {
+            summary.push(("Camera", camera));
+        }
+
+        if let Some(orientation) = self.orientation() {
+            summary.push((
+                "Orientation",
+                format!("{orientation} ({})", orientation_name(orientation)),
+            ));
+        }
+
+        for (label, tag) in [
+            ("Taken", Tag::DATE_TIME_ORIGINAL),
+            ("Digitized", Tag::DATE_TIME_DIGITIZED),
+            ("Modified", Tag::DATE_TIME),
+        ] {
+            if let Some(value) = self.value(tag) {
+                summary.push((label, value.to_string()));
+            }
+        }
+
+        if let Some((latitude, longitude)) = self.gps_position() {
+            summary.push(("GPS", format!("{latitude:.6}, {longitude:.6}")));
+        } else if self.get(Tag::GPS_IFD).is_some() {
+            summary.push(("GPS", "present, without a position".to_string()));
+        }
+
+        summary
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        let mut writer = Writer {
+            out: match self.byte_order {
+                ByteOrder::Little => b"II*\0".to_vec(),
+                ByteOrder::Big => b"MM\0*".to_vec(),
+            },
+            byte_order: self.byte_order,
+        };
+        writer.out.extend([0; 4]);
+
+        let mut next_field = 4;
+        for ifd in &self.ifds {
+            writer.align();
+            writer.patch(next_field, writer.out.len())?;
+            next_field = writer.ifd(ifd, self.thumbnail.as_deref())?;
+        }
+
+        Ok(writer.out)
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        Chunk::try_new(ChunkType::from_str(CHUNK_TYPE)?, self.to_bytes()?)
+    }
+}
+
+impl TryFrom<&[u8]> for Exif {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let byte_order = match bytes.get(..4) {
+            Some(b"II*\0") => ByteOrder::Little,
+            Some(b"MM\0*") => ByteOrder::Big,
+            _ => bail!("not TIFF data, expected `II*` or `MM*` at the start"),
+        };
+
+        let mut reader = Reader {
+            bytes,
+            byte_order,
+            visited: HashSet::new(),
+        };
+
+        let mut ifds = vec![];
+        let mut offset = reader.u32(4)?;
+        while offset != 0 {
+            let kind = match ifds.is_empty() {
+                true => IfdKind::Image,
+                false => IfdKind::Thumbnail,
+            };
+
+            let (ifd, next) = reader.ifd(offset, kind, 0)?;
+            ifds.push(ifd);
+            offset = next;
+        }
+
+        let thumbnail = match ifds.get(1) {
+            Some(ifd) => match (
+                ifd.find(Tag::THUMBNAIL_OFFSET),
+                ifd.find(Tag::THUMBNAIL_LENGTH),
+            ) {
+                (Some(offset), Some(length)) => {
+                    let number = |entry: &Entry| match entry.value(byte_order) {
+                        Value::Unsigned(values) => values.first().map(|&v| v as usize),
+                        _ => None,
+                    };
+                    let (Some(offset), Some(length)) = (number(offset), number(length)) else {
+                        bail!("invalid thumbnail offset or length");
+                    };
+                    Some(reader.slice(offset, length)?.to_vec())
+                }
+                _ => None,
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            byte_order,
+            ifds,
+            thumbnail,
+        })
+    }
+}
+
+impl Display for Exif {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (index, ifd) in self.ifds.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            ifd.fmt_with(f, self.byte_order)?;
+        }
+
+        if let Some(thumbnail) = &self.thumbnail {
+            write!(f, "\nThumbnail: {} bytes", thumbnail.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    byte_order: ByteOrder,
+    /// Offsets of the IFDs read so far, to stop on loops
+    visited: HashSet<u32>,
+}
+
+impl<'a> Reader<'a> {
+    fn slice(&self, offset: usize, length: usize) -> Result<&'a [u8]> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.bytes.get(offset..end))
+            .ok_or_else(|| anyhow!("offset {offset} is outside the Exif data"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        Ok(self.byte_order.u16(self.slice(offset, 2)?))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        Ok(self.byte_order.u32(self.slice(offset, 4)?))
+    }
+
+    /// Reads the IFD at `offset` and the IFDs it points to, returns it with the offset of the next one
+    fn ifd(&mut self, offset: u32, kind: IfdKind, depth: usize) -> Result<(Ifd, u32)> {
+        if depth > MAX_DEPTH || !self.visited.insert(offset) {
+            bail!("IFD at offset {offset} is nested too deep or loops back");
+        }
+
+        let offset = offset as usize;
+        let count = usize::from(self.u16(offset)?);
+        let mut entries = Vec::with_capacity(count);
+
+        for field in self.slice(offset + 2, count * 12)?.chunks_exact(12) {
+            let tag = Tag {
+                namespace: kind.namespace(),
+                number: self.byte_order.u16(field),
+            };
+            let field_type = self.byte_order.u16(&field[2..]);
+            let count = self.byte_order.u32(&field[4..]);
+            let value = &field[8..];
+
+            let pointer = tag
+                .sub_ifd()
+                .filter(|_| [4, 13].contains(&field_type) && count == 1);
+            if let Some(sub_kind) = pointer {
+                let (ifd, _) = self.ifd(self.byte_order.u32(value), sub_kind, depth + 1)?;
+                entries.push(Entry {
+                    tag,
+                    field_type,
+                    count,
+                    data: vec![],
+                    ifd: Some(ifd),
+                });
+                continue;
+            }
+
+            // readers have to skip types they don't know, without a size the
+            // value can't be found, let alone written back
+            let Some(size) = type_size(field_type) else {
+                continue;
+            };
+            let length = size
+                .checked_mul(count as usize)
+                .ok_or_else(|| anyhow!("{tag} is too long"))?;
+
+            let data = match length <= 4 {
+                true => value[..length].to_vec(),
+                false => self
+                    .slice(self.byte_order.u32(value) as usize, length)?
+                    .to_vec(),
+            };
+
+            entries.push(Entry {
+                tag,
+                field_type,
+                count,
+                data,
+                ifd: None,
+            });
+        }
+
+        let next = self.u32(offset + 2 + count * 12)?;
+
+        Ok((Ifd { kind, entries }, next))
+    }
+}
+
+struct Writer {
+    out: Vec<u8>,
+    byte_order: ByteOrder,
+}
+
+impl Writer {
+    /// Offsets have to be even
+    fn align(&mut self) {
+        if !self.out.len().is_multiple_of(2) {
+            self.out.push(0);
+        }
+    }
+
+    fn patch(&mut self, at: usize, offset: usize) -> Result<()> {
+        let offset = u32::try_from(offset).map_err(|_| anyhow!("Exif data is too large"))?;
+        self.out[at..at + 4].copy_from_slice(&self.byte_order.u32_bytes(offset));
+
+        Ok(())
+    }
+
+    /// Appends `data` and points the field at `at` to it
+    fn append(&mut self, at: usize, data: &[u8]) -> Result<()> {
+        self.align();
+        self.patch(at, self.out.len())?;
+        self.out.extend(data);
+
+        Ok(())
+    }
+
+    /// Writes `ifd` followed by its values and nested IFDs
+    ///
+    /// Returns where the offset of the next IFD goes.
+    fn ifd(&mut self, ifd: &Ifd, thumbnail: Option<&[u8]>) -> Result<usize> {
+        let count = u16::try_from(ifd.entries.len())
+            .map_err(|_| anyhow!("{} has too many entries", ifd.kind))?;
+        self.out.extend(self.byte_order.u16_bytes(count));
+
+        let start = self.out.len();
+        self.out.resize(start + ifd.entries.len() * 12 + 4, 0);
+
+        for (index, entry) in ifd.entries.iter().enumerate() {
+            let at = start + index * 12;
+            self.out[at..at + 2].copy_from_slice(&self.byte_order.u16_bytes(entry.tag.number));
+            self.out[at + 2..at + 4].copy_from_slice(&self.byte_order.u16_bytes(entry.field_type));
+            self.out[at + 4..at + 8].copy_from_slice(&self.byte_order.u32_bytes(entry.count));
+            let value = at + 8;
+
+            match (&entry.ifd, thumbnail) {
+                (Some(sub), _) => {
+                    self.align();
+                    self.patch(value, self.out.len())?;
+                    self.ifd(sub, None)?;
+                }
+                (None, Some(thumbnail))
+                    if ifd.kind == IfdKind::Thumbnail && entry.tag == Tag::THUMBNAIL_OFFSET =>
+                {
+                    self.append(value, thumbnail)?;
+                }
+                (None, _) if entry.data.len() <= 4 => {
+                    self.out[value..value + entry.data.len()].copy_from_slice(&entry.data);
+                }
+                (None, _) => self.append(value, &entry.data)?,
+            }
+        }
+
+        Ok(start + ifd.entries.len() * 12)
+    }
+}
+
+pub fn orientation_name(orientation: u16) -> &'static str {
+    match orientation {
+        1 => "normal",
+        2 => "mirrored horizontally",
+        3 => "rotated 180°",
+        4 => "mirrored vertically",
+        5 => "mirrored horizontally and rotated 270° clockwise",
+        6 => "rotated 90° clockwise",
+        7 => "mirrored horizontally and rotated 90° clockwise",
+        8 => "rotated 270° clockwise",
+        _ => "unknown",
+    }
+}
+
+/// Parses the `eXIf` chunk of `png`
+pub fn read(png: &Png) -> Result<Exif> {
+    match png.chunk_by_type(CHUNK_TYPE) {
+        Some(chunk) => Exif::try_from(chunk.data()),
+        None => bail!("No eXIf chunk found"),
+    }
+}
+
+/// Replaces the `eXIf` chunk of `png` with `exif`, keeping its position
+pub fn write(png: &mut Png, exif: &Exif) -> Result<()> {
+    let Some(index) = png
+        .chunks()
+        .iter()
+        .position(|c| c.chunk_type().to_string() == CHUNK_TYPE)
+    else {
+        bail!("No eXIf chunk found");
+    };
+
+    png.replace_chunk_at(index, exif.to_chunk()?)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Orientation and a GPS IFD with a latitude reference, little endian
+    const LITTLE_ENDIAN: [u8; 56] = [
+        b'I', b'I', 42, 0, 8, 0, 0, 0, // header, IFD0 at 8
+        2, 0, // two entries
+        0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, // Orientation, short 6
+        0x25, 0x88, 4, 0, 1, 0, 0, 0, 38, 0, 0, 0, // GPSInfo at 38
+        0, 0, 0, 0, // no next IFD
+        1, 0, // one entry
+        1, 0, 2, 0, 2, 0, 0, 0, b'N', 0, 0, 0, // GPSLatitudeRef "N"
+        0, 0, 0, 0, // no next IFD
+    ];
+
+    fn entry(tag: Tag, field_type: u16, count: u32, data: &[u8]) -> Entry {
+        Entry {
+            tag,
+            field_type,
+            count,
+            data: data.to_vec(),
+            ifd: None,
+        }
+    }
+
+    fn rationals(values: &[(u32, u32)]) -> Vec<u8> {
+        values
+            .iter()
+            .flat_map(|(n, d)| [n.to_be_bytes(), d.to_be_bytes()].concat())
+            .collect()
+    }
+
+    /// A camera photo taken in Berlin, big endian with a thumbnail
+    fn testing_exif() -> Exif {
+        let gps = Ifd {
+            kind: IfdKind::Gps,
+            entries: vec![
+                entry(Tag::GPS_LATITUDE_REF, 2, 2, b"N\0"),
+                entry(
+                    Tag::GPS_LATITUDE,
+                    5,
+                    3,
+                    &rationals(&[(52, 1), (31, 1), (12, 1)]),
+                ),
+                entry(Tag::GPS_LONGITUDE_REF, 2, 2, b"W\0"),
+                entry(
+                    Tag::GPS_LONGITUDE,
+                    5,
+                    3,
+                    &rationals(&[(13, 1), (24, 1), (0, 1)]),
+                ),
+            ],
+        };
+        let exif = Ifd {
+            kind: IfdKind::Exif,
+            entries: vec![entry(
+                Tag::DATE_TIME_ORIGINAL,
+                2,
+                20,
+                b"2024:05:06 07:08:09\0",
+            )],
+        };
+        let image = Ifd {
+            kind: IfdKind::Image,
+            entries: vec![
+                entry(Tag::MAKE, 2, 6, b"Canon\0"),
+                entry(Tag::MODEL, 2, 8, b"EOS R5 \0"),
+                entry(Tag::ORIENTATION, 3, 1, &[0, 8]),
+                Entry {
+                    ifd: Some(exif),
+                    ..entry(Tag::EXIF_IFD, 4, 1, &[])
+                },
+                Entry {
+                    ifd: Some(gps),
+                    ..entry(Tag::GPS_IFD, 4, 1, &[])
+                },
+            ],
+        };
+        let thumbnail = Ifd {
+            kind: IfdKind::Thumbnail,
+            entries: vec![
+                entry(Tag::THUMBNAIL_OFFSET, 4, 1, &[0; 4]),
+                entry(Tag::THUMBNAIL_LENGTH, 4, 1, &5u32.to_be_bytes()),
+            ],
+        };
+
+        Exif {
+            byte_order: ByteOrder::Big,
+            ifds: vec![image, thumbnail],
+            thumbnail: Some(vec![0xff, 0xd8, 1, 0xff, 0xd9]),
+        }
+    }
+
+    #[test]
+    fn test_parse_little_endian() {
+        let exif = Exif::try_from(LITTLE_ENDIAN.as_slice()).unwrap();
+
+        assert_eq!(exif.byte_order, ByteOrder::Little);
+        assert_eq!(exif.orientation(), Some(6));
+        assert_eq!(
+            exif.value(Tag::GPS_LATITUDE_REF),
+            Some(Value::Ascii("N".to_string()))
+        );
+        assert_eq!(exif.to_bytes().unwrap(), LITTLE_ENDIAN);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let exif = testing_exif();
+        let bytes = exif.to_bytes().unwrap();
+
+        assert_eq!(&bytes[..4], b"MM\0*");
+
+        // only the thumbnail offset changes, as it now points into the data
+        let parsed = Exif::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(parsed.ifds[0], exif.ifds[0]);
+        assert_eq!(parsed.thumbnail, exif.thumbnail);
+        assert_eq!(parsed.to_bytes().unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_summary() {
+        let summary = testing_exif().summary();
+
+        assert_eq!(
+            summary,
+            [
+                ("Camera", "Canon EOS R5".to_string()),
+                ("Orientation", "8 (rotated 270° clockwise)".to_string()),
+                ("Taken", "2024:05:06 07:08:09".to_string()),
+                ("GPS", "52.520000, -13.400000".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strip_gps() {
+        let mut exif = testing_exif();
+
+        assert!(exif.strip_gps());
+        assert!(!exif.strip_gps());
+
+        let exif = Exif::try_from(exif.to_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(exif.gps_position(), None);
+        assert!(exif.get(Tag::GPS_LATITUDE_REF).is_none());
+        assert_eq!(exif.orientation(), Some(8));
+        assert_eq!(
+            exif.thumbnail.as_deref(),
+            Some([0xff, 0xd8, 1, 0xff, 0xd9].as_slice())
+        );
+    }
+
+    #[test]
+    fn test_remove_tag() {
+        let mut exif = testing_exif();
+
+        assert_eq!(exif.remove_tag("DateTimeOriginal".parse().unwrap()), 1);
+        assert_eq!(exif.remove_tag("gps:2".parse().unwrap()), 1);
+        assert_eq!(exif.remove_tag("0x0112".parse().unwrap()), 1);
+        assert_eq!(exif.remove_tag(Tag::ORIENTATION), 0);
+        assert_eq!(exif.remove_tag(Tag::THUMBNAIL_OFFSET), 1);
+        assert_eq!(exif.thumbnail, None);
+
+        let exif = Exif::try_from(exif.to_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(exif.orientation(), None);
+        assert_eq!(exif.value(Tag::DATE_TIME_ORIGINAL), None);
+        assert!(exif.get(Tag::GPS_LONGITUDE).is_some());
+        assert_eq!(exif.camera().as_deref(), Some("Canon EOS R5"));
+    }
+
+    #[test]
+    fn test_tag_from_str() {
+        assert_eq!("orientation".parse::<Tag>().unwrap(), Tag::ORIENTATION);
+        assert_eq!("274".parse::<Tag>().unwrap(), Tag::ORIENTATION);
+        assert_eq!("gps:0x0004".parse::<Tag>().unwrap(), Tag::GPS_LONGITUDE);
+        assert!("foo:1".parse::<Tag>().is_err());
+        assert!("NotATag".parse::<Tag>().is_err());
+
+        assert_eq!(Tag::tiff(0xabcd).to_string(), "0xabcd");
+        assert_eq!(Tag::GPS_LATITUDE.to_string(), "GPSLatitude");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Exif::try_from(b"GIF89a".as_slice()).is_err());
+
+        let mut truncated = LITTLE_ENDIAN;
+        truncated[30] = 200;
+        assert!(Exif::try_from(truncated.as_slice()).is_err());
+
+        // the GPS IFD points back at IFD0
+        let mut looping = LITTLE_ENDIAN;
+        looping[30] = 8;
+        assert!(Exif::try_from(looping.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_skip_unknown_field_type() {
+        let mut unknown = LITTLE_ENDIAN;
+        // the field type of Orientation
+        unknown[12] = 99;
+
+        let exif = Exif::try_from(unknown.as_slice()).unwrap();
+        assert_eq!(exif.orientation(), None);
+        assert!(exif.get(Tag::GPS_LATITUDE_REF).is_some());
+        assert!(exif.to_bytes().is_ok());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Exif::try_from(LITTLE_ENDIAN.as_slice())
+                .unwrap()
+                .to_string(),
+            "IFD0:\n  Orientation: 6\nGPS:\n  GPSLatitudeRef: N"
+        );
+        assert_eq!(Value::Bytes(b"0230".to_vec()).to_string(), "\"0230\"");
+        assert_eq!(Value::Bytes(vec![1, 2]).to_string(), "01, 02");
+        assert_eq!(
+            Value::Rational(vec![(1, 200), (28, 10)]).to_string(),
+            "1/200, 28/10"
+        );
+    }
+
+    #[test]
+    fn test_read_and_write_png() {
+        let mut png = Png::from_chunks(vec![
+            Chunk::new("IHDR".parse().unwrap(), vec![]),
+            Chunk::new(
+                CHUNK_TYPE.parse().unwrap(),
+                testing_exif().to_bytes().unwrap(),
+            ),
+            Chunk::new("IEND".parse().unwrap(), vec![]),
+        ]);
+
+        let mut exif = read(&png).unwrap();
+        exif.strip_gps();
+        write(&mut png, &exif).unwrap();
+
+        assert_eq!(png.chunks()[1].chunk_type().to_string(), CHUNK_TYPE);
+        assert_eq!(read(&png).unwrap().gps_position(), None);
+
+        let mut still = Png::from_chunks(vec![]);
+        assert!(read(&still).is_err());
+        assert!(write(&mut still, &exif).is_err());
+    }
+}
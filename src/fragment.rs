@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::{bail, Result};
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+/// Marks chunk data as one part of a message split across chunks
+pub const MAGIC: [u8; 4] = *b"PNGf";
+
+const VERSION: u8 = 1;
+
+/// Magic, version, id, index, count and checksum
+pub const HEADER_LENGTH: usize = 21;
+
+/// Chunk data size used when none is configured
+pub const DEFAULT_MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+const CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// One part of a split message, borrowed from the chunk data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fragment<'a> {
+    /// Shared by all parts of the same message
+    pub id: u32,
+    pub index: u32,
+    pub count: u32,
+    /// Crc of the whole message
+    pub checksum: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> Fragment<'a> {
+    /// Parses chunk data written by [`split`]
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        if !is_fragment(data) {
+            bail!("not a message fragment");
+        }
+
+        if data.len() < HEADER_LENGTH {
+            bail!("fragment header is truncated");
+        }
+
+        if data[4] != VERSION {
+            bail!("unsupported fragment version {}", data[4]);
+        }
+
+        let word = |i: usize| u32::from_be_bytes(data[i..i + 4].try_into().unwrap());
+
+        let fragment = Self {
+            id: word(5),
+            index: word(9),
+            count: word(13),
+            checksum: word(17),
+            data: &data[HEADER_LENGTH..],
+        };
+
+        if fragment.index >= fragment.count {
+            bail!(
+                "fragment index {} out of range for {} fragments",
+                fragment.index,
+                fragment.count
+            );
+        }
+
+        Ok(fragment)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        MAGIC
+            .iter()
+            .copied()
+            .chain([VERSION])
+            .chain(self.id.to_be_bytes())
+            .chain(self.index.to_be_bytes())
+            .chain(self.count.to_be_bytes())
+            .chain(self.checksum.to_be_bytes())
+            .chain(self.data.iter().copied())
+            .collect()
+    }
+}
+
+/// Returns whether `data` is a fragment of a split message
+pub fn is_fragment(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Splits `message` into chunks whose data is at most `max_chunk_size` bytes long
+pub fn split(chunk_type: &ChunkType, message: &[u8], max_chunk_size: usize) -> Result<Vec<Chunk>> {
+    if max_chunk_size <= HEADER_LENGTH {
+        bail!("maximum chunk size has to be larger than {HEADER_LENGTH} bytes");
+    }
+
+    if max_chunk_size > Chunk::MAX_LENGTH {
+        bail!(
+            "maximum chunk size can't exceed {} bytes",
+            Chunk::MAX_LENGTH
+        );
+    }
+
+    let mut id = [0u8; 4];
+    getrandom::getrandom(&mut id)?;
+
+    let parts: Vec<&[u8]> = message.chunks(max_chunk_size - HEADER_LENGTH).collect();
+    let count = u32::try_from(parts.len())?;
+    let checksum = CRC.checksum(message);
+
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let fragment = Fragment {
+                id: u32::from_be_bytes(id),
+                index: index.try_into()?,
+                count,
+                checksum,
+                data,
+            };
+
+            Chunk::try_new(chunk_type.clone(), fragment.as_bytes())
+        })
+        .collect()
+}
+
+/// A message put back together from its fragments
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Reassembled {
+    /// Index of the chunk holding the first fragment seen
+    pub chunk_index: usize,
+    pub message: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Group {
+    chunk_index: usize,
+    count: u32,
+    checksum: u32,
+    parts: BTreeMap<u32, Vec<u8>>,
+}
+
+/// Collects fragments in any order and returns each message once it is complete
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    groups: HashMap<u32, Group>,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a fragment found in the chunk at `chunk_index`
+    pub fn push(&mut self, chunk_index: usize, fragment: Fragment) -> Result<Option<Reassembled>> {
+        let group = self.groups.entry(fragment.id).or_insert_with(|| Group {
+            chunk_index,
+            count: fragment.count,
+            checksum: fragment.checksum,
+            parts: BTreeMap::new(),
+        });
+
+        if group.count != fragment.count || group.checksum != fragment.checksum {
+            bail!(
+                "fragment {} doesn't match the other fragments of its message",
+                fragment.index
+            );
+        }
+
+        if group
+            .parts
+            .insert(fragment.index, fragment.data.to_vec())
+            .is_some()
+        {
+            bail!("duplicate fragment {}", fragment.index);
+        }
+
+        if group.parts.len() < group.count as usize {
+            return Ok(None);
+        }
+
+        let group = self.groups.remove(&fragment.id).unwrap();
+        let message: Vec<u8> = group.parts.into_values().flatten().collect();
+
+        if CRC.checksum(&message) != group.checksum {
+            bail!("reassembled message doesn't match its checksum");
+        }
+
+        Ok(Some(Reassembled {
+            chunk_index: group.chunk_index,
+            message,
+        }))
+    }
+
+    /// Messages that are still missing fragments, as `(received, expected)`
+    pub fn incomplete(&self) -> Vec<(usize, u32)> {
+        self.groups
+            .values()
+            .map(|g| (g.parts.len(), g.count))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk_type() -> ChunkType {
+        ChunkType::from_str("ruSt").unwrap()
+    }
+
+    fn testing_message() -> Vec<u8> {
+        (0..1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_split() {
+        let chunks = split(&chunk_type(), &testing_message(), 121).unwrap();
+
+        assert_eq!(chunks.len(), 10);
+        assert!(chunks.iter().all(|c| c.length() <= 121));
+        assert!(chunks.iter().all(|c| is_fragment(c.data())));
+
+        let fragment = Fragment::parse(chunks[3].data()).unwrap();
+        assert_eq!(fragment.index, 3);
+        assert_eq!(fragment.count, 10);
+        assert_eq!(fragment.data, &testing_message()[300..400]);
+    }
+
+    #[test]
+    fn test_reassemble_in_any_order() {
+        let chunks = split(&chunk_type(), &testing_message(), 121).unwrap();
+        let mut reassembler = Reassembler::new();
+
+        for (i, chunk) in chunks.iter().enumerate().skip(1).rev() {
+            let fragment = Fragment::parse(chunk.data()).unwrap();
+            assert_eq!(reassembler.push(i, fragment).unwrap(), None);
+        }
+        assert_eq!(reassembler.incomplete(), vec![(9, 10)]);
+
+        let fragment = Fragment::parse(chunks[0].data()).unwrap();
+        let reassembled = reassembler.push(0, fragment).unwrap().unwrap();
+
+        assert_eq!(reassembled.message, testing_message());
+        assert_eq!(reassembled.chunk_index, 9);
+        assert!(reassembler.incomplete().is_empty());
+    }
+
+    #[test]
+    fn test_reassemble_corrupted() {
+        let chunks = split(&chunk_type(), &testing_message(), 521).unwrap();
+        let mut data = chunks[1].data().to_vec();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+
+        let mut reassembler = Reassembler::new();
+        let first = Fragment::parse(chunks[0].data()).unwrap();
+        reassembler.push(0, first).unwrap();
+
+        let second = Fragment::parse(&data).unwrap();
+        assert!(reassembler.push(1, second).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_fragment() {
+        let chunks = split(&chunk_type(), &testing_message(), 521).unwrap();
+        let fragment = Fragment::parse(chunks[0].data()).unwrap();
+
+        let mut reassembler = Reassembler::new();
+        reassembler.push(0, fragment).unwrap();
+        assert!(reassembler.push(1, fragment).is_err());
+    }
+
+    #[test]
+    fn test_invalid_sizes() {
+        assert!(split(&chunk_type(), b"message", HEADER_LENGTH).is_err());
+        assert!(split(&chunk_type(), b"message", Chunk::MAX_LENGTH + 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Fragment::parse(b"PNGf").is_err());
+        assert!(Fragment::parse(b"plain message that is long enough").is_err());
+
+        let chunks = split(&chunk_type(), b"message", 100).unwrap();
+        let mut data = chunks[0].data().to_vec();
+        data[9..13].copy_from_slice(&5u32.to_be_bytes());
+        assert!(Fragment::parse(&data).is_err());
+    }
+}
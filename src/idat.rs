@@ -0,0 +1,784 @@
+use std::fmt::Display;
+
+use anyhow::{anyhow, bail, Error, Result};
+use flate2::Compression;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    ihdr::{ColorType, Ihdr, InterlaceMethod},
+    png::{Png, Position},
+    zlib,
+};
+
+pub const CHUNK_TYPE: &str = "IDAT";
+
+/// Data size of the `IDAT` chunks written when replacing the image data
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Zlib compression level used when none is configured
+pub const DEFAULT_LEVEL: u32 = 6;
+
+/// First column, first row, column step and row step of the seven Adam7 passes
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+/// The filter applied to a scanline before compression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => FilterType::None,
+            1 => FilterType::Sub,
+            2 => FilterType::Up,
+            3 => FilterType::Average,
+            4 => FilterType::Paeth,
+            _ => bail!("invalid filter type {value}"),
+        })
+    }
+}
+
+impl Display for FilterType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FilterType::None => "none",
+            FilterType::Sub => "sub",
+            FilterType::Up => "up",
+            FilterType::Average => "average",
+            FilterType::Paeth => "paeth",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// How the filter of every row is picked when encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// The same filter for every row
+    Fixed(FilterType),
+    /// The filter giving the smallest sum of absolute differences, row by row
+    Adaptive,
+}
+
+impl Display for FilterStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterStrategy::Fixed(filter) => write!(f, "{filter}"),
+            FilterStrategy::Adaptive => write!(f, "adaptive"),
+        }
+    }
+}
+
+/// A reduced image stored in the image data, the whole image if it isn't interlaced
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pass {
+    /// Column of the first pixel in the full image
+    pub x: usize,
+    /// Row of the first pixel in the full image
+    pub y: usize,
+    /// Columns between two pixels in the full image
+    pub dx: usize,
+    /// Rows between two pixels in the full image
+    pub dy: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Pass {
+    /// Bytes in a single unfiltered row of this pass
+    pub fn bytes_per_row(&self, ihdr: &Ihdr) -> usize {
+        (self.width * ihdr.bits_per_pixel()).div_ceil(8)
+    }
+}
+
+/// The passes of an image in stored order, leaving out empty ones as they aren't stored
+pub fn passes(ihdr: &Ihdr) -> Vec<Pass> {
+    let (width, height) = (ihdr.width as usize, ihdr.height as usize);
+    let steps = match ihdr.interlace_method {
+        InterlaceMethod::None => &[(0, 0, 1, 1)][..],
+        InterlaceMethod::Adam7 => &ADAM7[..],
+    };
+
+    steps
+        .iter()
+        .map(|&(x, y, dx, dy)| Pass {
+            x,
+            y,
+            dx,
+            dy,
+            width: width.saturating_sub(x).div_ceil(dx),
+            height: height.saturating_sub(y).div_ceil(dy),
+        })
+        .filter(|p| p.width > 0 && p.height > 0)
+        .collect()
+}
+
+/// Unfiltered rows as they are stored, pass after pass for interlaced images
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scanlines {
+    pub ihdr: Ihdr,
+    /// The filter of every row, used when filtering again
+    pub filters: Vec<FilterType>,
+    /// All rows back to back, without their filter type byte
+    pub data: Vec<u8>,
+}
+
+impl Scanlines {
+    /// Inflates and unfilters the `IDAT` stream of `png`
+    pub fn read(png: &Png) -> Result<Self> {
+        Self::decode(png.ihdr()?, &image_data(png))
+    }
+
+    /// Inflates and unfilters a zlib stream of image data described by `ihdr`
+    pub fn decode(ihdr: Ihdr, zlib: &[u8]) -> Result<Self> {
+        ihdr.validate()?;
+
+        let passes = passes(&ihdr);
+        let expected = passes
+            .iter()
+            .try_fold(0usize, |total, p| {
+                p.height
+                    .checked_mul(p.bytes_per_row(&ihdr) + 1)
+                    .and_then(|length| total.checked_add(length))
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "image of {}x{} pixels is too large",
+                    ihdr.width,
+                    ihdr.height
+                )
+            })?;
+
+        // the header says how much data there is, anything more is rejected
+        let filtered = zlib::decompress(zlib, expected)?;
+        if filtered.len() < expected {
+            bail!(
+                "image data is truncated, expected {expected} bytes but found {}",
+                filtered.len()
+            );
+        }
+
+        let bpp = filter_bpp(&ihdr);
+        let mut filters = vec![];
+        let mut data = Vec::with_capacity(expected);
+        let mut lines = &filtered[..];
+
+        for pass in &passes {
+            let row_length = pass.bytes_per_row(&ihdr);
+            let mut prev = vec![0u8; row_length];
+
+            for _ in 0..pass.height {
+                let (line, rest) = lines.split_at(row_length + 1);
+                lines = rest;
+
+                let filter = FilterType::try_from(line[0])?;
+                let mut row = line[1..].to_vec();
+                unfilter(filter, bpp, &prev, &mut row);
+
+                data.extend_from_slice(&row);
+                filters.push(filter);
+                prev = row;
+            }
+        }
+
+        Ok(Self {
+            ihdr,
+            filters,
+            data,
+        })
+    }
+
+    /// Every pass with its rows
+    fn stored_passes(&self) -> Vec<(Pass, &[u8])> {
+        let mut data = &self.data[..];
+
+        passes(&self.ihdr)
+            .into_iter()
+            .map(|pass| {
+                let length = (pass.height * pass.bytes_per_row(&self.ihdr)).min(data.len());
+                let (rows, rest) = data.split_at(length);
+                data = rest;
+                (pass, rows)
+            })
+            .collect()
+    }
+
+    /// Picks the filter of every row with `strategy`
+    ///
+    /// As the spec recommends, indexed images and those with less than 8 bits
+    /// per sample aren't filtered when adapting.
+    pub fn refilter(&mut self, strategy: FilterStrategy) {
+        let strategy = match strategy {
+            FilterStrategy::Adaptive
+                if self.ihdr.color_type == ColorType::Indexed || self.ihdr.bit_depth < 8 =>
+            {
+                FilterStrategy::Fixed(FilterType::None)
+            }
+            strategy => strategy,
+        };
+
+        let bpp = filter_bpp(&self.ihdr);
+        let mut filters = Vec::with_capacity(self.filters.len());
+
+        for (pass, rows) in self.stored_passes() {
+            let zeros = vec![0u8; pass.bytes_per_row(&self.ihdr)];
+            let mut prev = &zeros[..];
+
+            for row in rows.chunks_exact(zeros.len()) {
+                filters.push(choose_filter(strategy, bpp, prev, row));
+                prev = row;
+            }
+        }
+
+        self.filters = filters;
+    }
+
+    /// Filters every row with its filter and compresses the result at `level`, 0 to 9
+    pub fn to_zlib(&self, level: u32) -> Result<Vec<u8>> {
+        let bpp = filter_bpp(&self.ihdr);
+        let mut filtered = Vec::with_capacity(self.data.len() + self.filters.len());
+        let mut filters = self.filters.iter();
+
+        for (pass, rows) in self.stored_passes() {
+            let zeros = vec![0u8; pass.bytes_per_row(&self.ihdr)];
+            let mut prev = &zeros[..];
+
+            for row in rows.chunks_exact(zeros.len()) {
+                let Some(&filter) = filters.next() else {
+                    bail!(
+                        "missing filter for row {}",
+                        filtered.len() / (zeros.len() + 1)
+                    );
+                };
+
+                filtered.push(filter as u8);
+                filter_row(filter, bpp, prev, row, &mut filtered);
+                prev = row;
+            }
+        }
+
+        zlib::compress(&filtered, Compression::new(level))
+    }
+
+    /// Deinterlaces the rows into the full image
+    pub fn to_image(&self) -> RawImage {
+        if self.ihdr.interlace_method == InterlaceMethod::None {
+            return RawImage {
+                ihdr: self.ihdr,
+                pixels: self.data.clone(),
+            };
+        }
+
+        let bits = self.ihdr.bits_per_pixel();
+        let row_length = self.ihdr.bytes_per_row();
+        let mut pixels = vec![0u8; row_length * self.ihdr.height as usize];
+
+        for (pass, rows) in self.stored_passes() {
+            for (py, row) in rows
+                .chunks_exact(pass.bytes_per_row(&self.ihdr))
+                .enumerate()
+            {
+                let y = pass.y + py * pass.dy;
+                let image_row = &mut pixels[y * row_length..(y + 1) * row_length];
+
+                for px in 0..pass.width {
+                    copy_pixel(bits, row, px, image_row, pass.x + px * pass.dx);
+                }
+            }
+        }
+
+        RawImage {
+            ihdr: self.ihdr,
+            pixels,
+        }
+    }
+}
+
+/// Unfiltered and deinterlaced pixels, row after row
+///
+/// Pixels of less than 8 bits are packed, rows always start on a new byte.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RawImage {
+    pub ihdr: Ihdr,
+    pub pixels: Vec<u8>,
+}
+
+impl RawImage {
+    /// Decodes the `IDAT` stream of `png`
+    pub fn decode(png: &Png) -> Result<Self> {
+        Ok(Scanlines::read(png)?.to_image())
+    }
+
+    /// Bytes of row `y`
+    pub fn row(&self, y: usize) -> &[u8] {
+        let length = self.ihdr.bytes_per_row();
+        &self.pixels[y * length..(y + 1) * length]
+    }
+
+    /// Interlaces the pixels as `ihdr` says and picks filters with `strategy`
+    pub fn to_scanlines(&self, strategy: FilterStrategy) -> Scanlines {
+        let data = match self.ihdr.interlace_method {
+            InterlaceMethod::None => self.pixels.clone(),
+            InterlaceMethod::Adam7 => self.interlace(),
+        };
+
+        let mut scanlines = Scanlines {
+            ihdr: self.ihdr,
+            filters: vec![],
+            data,
+        };
+        scanlines.refilter(strategy);
+
+        scanlines
+    }
+
+    /// Filters and compresses the pixels into image data
+    pub fn encode(&self, strategy: FilterStrategy, level: u32) -> Result<Vec<u8>> {
+        self.to_scanlines(strategy).to_zlib(level)
+    }
+
+    fn interlace(&self) -> Vec<u8> {
+        let bits = self.ihdr.bits_per_pixel();
+        let mut data = vec![];
+
+        for pass in passes(&self.ihdr) {
+            for py in 0..pass.height {
+                let image_row = self.row(pass.y + py * pass.dy);
+                let mut row = vec![0u8; pass.bytes_per_row(&self.ihdr)];
+
+                for px in 0..pass.width {
+                    copy_pixel(bits, image_row, pass.x + px * pass.dx, &mut row, px);
+                }
+
+                data.extend(row);
+            }
+        }
+
+        data
+    }
+}
+
+/// Copies pixel `from_x` of the row `from` to pixel `to_x` of the row `to`
+fn copy_pixel(bits: usize, from: &[u8], from_x: usize, to: &mut [u8], to_x: usize) {
+    if bits >= 8 {
+        let length = bits / 8;
+        to[to_x * length..(to_x + 1) * length]
+            .copy_from_slice(&from[from_x * length..(from_x + 1) * length]);
+        return;
+    }
+
+    let mask = ((1u16 << bits) - 1) as u8;
+    let shift = |x: usize| 8 - bits - (x * bits) % 8;
+
+    let value = (from[from_x * bits / 8] >> shift(from_x)) & mask;
+    let byte = &mut to[to_x * bits / 8];
+    *byte = (*byte & !(mask << shift(to_x))) | (value << shift(to_x));
+}
+
+/// The `IDAT` data of `png`, concatenated in order
+pub fn image_data(png: &Png) -> Vec<u8> {
+    png.chunks()
+        .iter()
+        .filter(|c| c.chunk_type().to_string() == CHUNK_TYPE)
+        .flat_map(|c| c.data().iter().copied())
+        .collect()
+}
+
+/// Replaces the `IDAT` chunks of `png` with `data`, split into chunks of `chunk_size`
+///
+/// The new chunks take the place of the first old one.
+pub fn replace_image_data(png: &mut Png, data: &[u8], chunk_size: usize) -> Result<()> {
+    let indices: Vec<_> = png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.chunk_type().to_string() == CHUNK_TYPE)
+        .map(|(i, _)| i)
+        .collect();
+
+    let Some(&first) = indices.first() else {
+        bail!("IDAT chunk not found");
+    };
+
+    for index in indices.into_iter().rev() {
+        png.remove_chunk_at(index)?;
+    }
+
+    let chunk_type: ChunkType = CHUNK_TYPE.parse()?;
+    let mut position = match first {
+        0 => bail!("IDAT chunk can't come first"),
+        _ => Position::AfterChunk(first - 1),
+    };
+
+    for data in data.chunks(chunk_size.max(1)) {
+        let index =
+            png.insert_chunk(Chunk::try_new(chunk_type.clone(), data.to_vec())?, position)?;
+        position = Position::AfterChunk(index);
+    }
+
+    Ok(())
+}
+
+/// Distance in bytes to the corresponding byte of the previous pixel, at least 1
+fn filter_bpp(ihdr: &Ihdr) -> usize {
+    ihdr.bits_per_pixel().div_ceil(8)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let pa = (p - i16::from(a)).abs();
+    let pb = (p - i16::from(b)).abs();
+    let pc = (p - i16::from(c)).abs();
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The value a filter predicts for `row[i]` from the bytes before and above it
+fn predict(filter: FilterType, bpp: usize, prev: &[u8], row: &[u8], i: usize) -> u8 {
+    let a = if i >= bpp { row[i - bpp] } else { 0 };
+    let b = prev[i];
+    let c = if i >= bpp { prev[i - bpp] } else { 0 };
+
+    match filter {
+        FilterType::None => 0,
+        FilterType::Sub => a,
+        FilterType::Up => b,
+        FilterType::Average => ((u16::from(a) + u16::from(b)) / 2) as u8,
+        FilterType::Paeth => paeth(a, b, c),
+    }
+}
+
+/// Undoes `filter` in place, `prev` is the already unfiltered row above
+fn unfilter(filter: FilterType, bpp: usize, prev: &[u8], row: &mut [u8]) {
+    for i in 0..row.len() {
+        row[i] = row[i].wrapping_add(predict(filter, bpp, prev, row, i));
+    }
+}
+
+fn choose_filter(strategy: FilterStrategy, bpp: usize, prev: &[u8], row: &[u8]) -> FilterType {
+    match strategy {
+        FilterStrategy::Fixed(filter) => filter,
+        FilterStrategy::Adaptive => {
+            let mut filtered = Vec::with_capacity(row.len());
+            let mut cost = |filter: FilterType| -> u64 {
+                filtered.clear();
+                filter_row(filter, bpp, prev, row, &mut filtered);
+                filtered
+                    .iter()
+                    .map(|b| u64::from((*b as i8).unsigned_abs()))
+                    .sum()
+            };
+
+            FilterType::ALL
+                .into_iter()
+                .min_by_key(|f| cost(*f))
+                .unwrap_or(FilterType::None)
+        }
+    }
+}
+
+/// Appends `row` filtered with `filter` to `out`
+fn filter_row(filter: FilterType, bpp: usize, prev: &[u8], row: &[u8], out: &mut Vec<u8>) {
+    for (i, byte) in row.iter().enumerate() {
+        out.push(byte.wrapping_sub(predict(filter, bpp, prev, row, i)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::PNG_FILE;
+
+    fn rgb_ihdr(width: u32, height: u32) -> Ihdr {
+        Ihdr {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    fn interlaced_ihdr(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> Ihdr {
+        Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::Adam7,
+        }
+    }
+
+    fn testing_image(ihdr: Ihdr) -> RawImage {
+        let length = ihdr.bytes_per_row() * ihdr.height as usize;
+        let pixels = (0..length).map(|i| (i * 37 + i / 5) as u8).collect();
+
+        RawImage { ihdr, pixels }
+    }
+
+    fn png_with_image_data(ihdr: &Ihdr, data: &[u8]) -> Png {
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new("IDAT".parse().unwrap(), data.to_vec()),
+            Chunk::new("IEND".parse().unwrap(), vec![]),
+        ])
+    }
+
+    fn chunk_types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_round_trip() {
+        let prev = [10, 200, 30, 40, 250, 60];
+        let row = [1, 255, 3, 128, 0, 77];
+
+        for filter in 0..=4 {
+            let filter = FilterType::try_from(filter).unwrap();
+            let mut filtered = vec![];
+            filter_row(filter, 3, &prev, &row, &mut filtered);
+
+            unfilter(filter, 3, &prev, &mut filtered);
+            assert_eq!(filtered, row, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn test_paeth() {
+        assert_eq!(paeth(10, 20, 10), 20);
+        assert_eq!(paeth(20, 10, 10), 20);
+        assert_eq!(paeth(10, 10, 20), 10);
+    }
+
+    #[test]
+    fn test_read_scanlines() {
+        let ihdr = rgb_ihdr(2, 2);
+        // row 0 unfiltered, row 1 stored as the difference to row 0
+        let filtered = [0, 1, 2, 3, 4, 5, 6, 2, 1, 1, 1, 1, 1, 1];
+        let png = png_with_image_data(
+            &ihdr,
+            &zlib::compress(&filtered, Compression::default()).unwrap(),
+        );
+
+        let scanlines = Scanlines::read(&png).unwrap();
+        assert_eq!(scanlines.filters, [FilterType::None, FilterType::Up]);
+        assert_eq!(scanlines.data, [1, 2, 3, 4, 5, 6, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn test_read_truncated_scanlines() {
+        let ihdr = rgb_ihdr(2, 2);
+        let png = png_with_image_data(
+            &ihdr,
+            &zlib::compress(&[0, 1, 2, 3], Compression::default()).unwrap(),
+        );
+
+        assert!(Scanlines::read(&png).is_err());
+    }
+
+    #[test]
+    fn test_read_huge_image() {
+        let mut ihdr = rgb_ihdr(0x7fff_ffff, 0x7fff_ffff);
+        ihdr.color_type = ColorType::Rgba;
+        let data = zlib::compress(&[0; 100], Compression::default()).unwrap();
+        assert!(Scanlines::decode(ihdr, &data).is_err());
+
+        // the size of the image data doesn't fit in a usize
+        ihdr.bit_depth = 16;
+        assert!(Scanlines::decode(ihdr, &data).is_err());
+    }
+
+    #[test]
+    fn test_scanlines_round_trip() {
+        let ihdr = rgb_ihdr(3, 2);
+        let filtered = [
+            1, 10, 20, 30, 1, 1, 1, 2, 2, 2, //
+            4, 5, 5, 5, 0, 0, 0, 9, 9, 9,
+        ];
+        let mut png = png_with_image_data(
+            &ihdr,
+            &zlib::compress(&filtered, Compression::default()).unwrap(),
+        );
+
+        let scanlines = Scanlines::read(&png).unwrap();
+        replace_image_data(&mut png, &scanlines.to_zlib(DEFAULT_LEVEL).unwrap(), 4).unwrap();
+
+        assert_eq!(zlib::decompress(&image_data(&png), 100).unwrap(), filtered);
+        assert_eq!(Scanlines::read(&png).unwrap(), scanlines);
+    }
+
+    #[test]
+    fn test_passes() {
+        let sizes = |ihdr: &Ihdr| -> Vec<(usize, usize)> {
+            passes(ihdr).iter().map(|p| (p.width, p.height)).collect()
+        };
+
+        assert_eq!(sizes(&rgb_ihdr(5, 3)), [(5, 3)]);
+        assert_eq!(
+            sizes(&interlaced_ihdr(8, 8, 8, ColorType::Rgb)),
+            [(1, 1), (1, 1), (2, 1), (2, 2), (4, 2), (4, 4), (8, 4)]
+        );
+        // the passes starting right of or below the only pixel are left out
+        assert_eq!(sizes(&interlaced_ihdr(1, 1, 8, ColorType::Rgb)), [(1, 1)]);
+        assert_eq!(
+            sizes(&interlaced_ihdr(3, 2, 8, ColorType::Rgb)),
+            [(1, 1), (1, 1), (1, 1), (3, 1)]
+        );
+    }
+
+    #[test]
+    fn test_adam7_pass_order() {
+        let ihdr = interlaced_ihdr(8, 8, 8, ColorType::Grayscale);
+        let image = testing_image(ihdr);
+        let scanlines = image.to_scanlines(FilterStrategy::Fixed(FilterType::None));
+
+        assert_eq!(scanlines.filters.len(), 15);
+        assert_eq!(scanlines.data.len(), 64);
+        // the first two passes hold the pixels at (0, 0) and (4, 0)
+        assert_eq!(scanlines.data[..2], [image.row(0)[0], image.row(0)[4]]);
+        // the last pass is every odd row
+        assert_eq!(scanlines.data[32..40], *image.row(1));
+    }
+
+    #[test]
+    fn test_encode_and_decode() {
+        let ihdrs = [
+            rgb_ihdr(7, 5),
+            interlaced_ihdr(13, 11, 8, ColorType::Rgba),
+            interlaced_ihdr(9, 10, 16, ColorType::GrayscaleAlpha),
+            interlaced_ihdr(11, 9, 1, ColorType::Grayscale),
+            interlaced_ihdr(5, 6, 2, ColorType::Indexed),
+            interlaced_ihdr(3, 17, 4, ColorType::Grayscale),
+        ];
+
+        for ihdr in ihdrs {
+            let mut image = testing_image(ihdr);
+            // padding bits at the end of a row aren't stored when interlaced
+            let padding = ihdr.bytes_per_row() * 8 - ihdr.width as usize * ihdr.bits_per_pixel();
+            for y in 0..ihdr.height as usize {
+                let last = (y + 1) * ihdr.bytes_per_row() - 1;
+                image.pixels[last] &= !((1u16 << padding) - 1) as u8;
+            }
+
+            for strategy in [
+                FilterStrategy::Adaptive,
+                FilterStrategy::Fixed(FilterType::Paeth),
+                FilterStrategy::Fixed(FilterType::Average),
+            ] {
+                let data = image.encode(strategy, DEFAULT_LEVEL).unwrap();
+                let decoded = Scanlines::decode(ihdr, &data).unwrap().to_image();
+
+                assert_eq!(decoded, image, "{ihdr:?} {strategy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_adaptive_filters() {
+        let ihdr = rgb_ihdr(4, 2);
+        // a gradient is predicted best from the left, a repeated row from above
+        let pixels: Vec<u8> = (0..12).chain(0..12).collect();
+        let image = RawImage { ihdr, pixels };
+
+        let scanlines = image.to_scanlines(FilterStrategy::Adaptive);
+        assert_eq!(scanlines.filters, [FilterType::Sub, FilterType::Up]);
+
+        let ihdr = interlaced_ihdr(4, 2, 8, ColorType::Indexed);
+        let scanlines = testing_image(ihdr).to_scanlines(FilterStrategy::Adaptive);
+        assert!(scanlines.filters.iter().all(|f| *f == FilterType::None));
+    }
+
+    #[test]
+    fn test_refilter_keeps_pixels() {
+        let ihdr = interlaced_ihdr(6, 6, 8, ColorType::Rgb);
+        let mut scanlines = testing_image(ihdr).to_scanlines(FilterStrategy::Adaptive);
+        let data = scanlines.to_zlib(DEFAULT_LEVEL).unwrap();
+
+        scanlines.refilter(FilterStrategy::Fixed(FilterType::Sub));
+        assert!(scanlines.filters.iter().all(|f| *f == FilterType::Sub));
+
+        let decoded = Scanlines::decode(ihdr, &scanlines.to_zlib(9).unwrap()).unwrap();
+        assert_eq!(decoded.data, Scanlines::decode(ihdr, &data).unwrap().data);
+    }
+
+    #[test]
+    fn test_decode_image_data() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let scanlines = Scanlines::read(&png).unwrap();
+        assert_eq!(scanlines.filters.len(), 50);
+        assert_eq!(scanlines.data.len(), 50 * 50 * 4);
+
+        let image = scanlines.to_image();
+        let data = image.encode(FilterStrategy::Adaptive, 9).unwrap();
+        replace_image_data(&mut png, &data, DEFAULT_CHUNK_SIZE).unwrap();
+
+        assert_eq!(RawImage::decode(&png).unwrap(), image);
+    }
+
+    #[test]
+    fn test_replace_image_data() {
+        let ihdr = rgb_ihdr(1, 1);
+        let mut png = Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new("IDAT".parse().unwrap(), vec![1, 2]),
+            Chunk::new("IDAT".parse().unwrap(), vec![3]),
+            Chunk::new("ruSt".parse().unwrap(), vec![]),
+            Chunk::new("IEND".parse().unwrap(), vec![]),
+        ]);
+
+        replace_image_data(&mut png, &[9, 8, 7, 6, 5], 2).unwrap();
+
+        assert_eq!(
+            chunk_types(&png),
+            ["IHDR", "IDAT", "IDAT", "IDAT", "ruSt", "IEND"]
+        );
+        assert_eq!(image_data(&png), [9, 8, 7, 6, 5]);
+    }
+
+    #[test]
+    fn test_replace_missing_image_data() {
+        let mut png = Png::from_chunks(vec![rgb_ihdr(1, 1).to_chunk()]);
+        assert!(replace_image_data(&mut png, &[1], 2).is_err());
+    }
+}
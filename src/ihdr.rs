@@ -0,0 +1,298 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+use serde::Serialize;
+
+use crate::{chunk::Chunk, chunk_type::ChunkType};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ColorType {
+    Grayscale = 0,
+    Rgb = 2,
+    Indexed = 3,
+    GrayscaleAlpha = 4,
+    Rgba = 6,
+}
+
+impl ColorType {
+    /// Number of samples per pixel
+    pub fn channels(&self) -> u8 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    /// Bit depths the spec allows for this color type
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => ColorType::Grayscale,
+            2 => ColorType::Rgb,
+            3 => ColorType::Indexed,
+            4 => ColorType::GrayscaleAlpha,
+            6 => ColorType::Rgba,
+            _ => bail!("invalid color type {value}"),
+        })
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "Grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "Indexed",
+            ColorType::GrayscaleAlpha => "Grayscale + Alpha",
+            ColorType::Rgba => "RGBA",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum InterlaceMethod {
+    None = 0,
+    Adam7 = 1,
+}
+
+impl TryFrom<u8> for InterlaceMethod {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => InterlaceMethod::None,
+            1 => InterlaceMethod::Adam7,
+            _ => bail!("invalid interlace method {value}"),
+        })
+    }
+}
+
+/// The image header, always the first chunk of a PNG
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: InterlaceMethod,
+}
+
+impl Ihdr {
+    pub const CHUNK_TYPE: &'static str = "IHDR";
+    const LENGTH: usize = 13;
+    const MAX_DIMENSION: u32 = i32::MAX as u32;
+
+    /// Bits used by a single pixel
+    pub fn bits_per_pixel(&self) -> usize {
+        usize::from(self.bit_depth) * usize::from(self.color_type.channels())
+    }
+
+    /// Bytes in a single unfiltered scanline of the full image
+    pub fn bytes_per_row(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Checks the fields against the ranges and combinations the spec allows
+    pub fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            bail!("image dimensions must not be zero");
+        }
+
+        if self.width > Self::MAX_DIMENSION || self.height > Self::MAX_DIMENSION {
+            bail!("image dimensions must not exceed {}", Self::MAX_DIMENSION);
+        }
+
+        if !self
+            .color_type
+            .allowed_bit_depths()
+            .contains(&self.bit_depth)
+        {
+            bail!(
+                "bit depth {} is not allowed for color type {}",
+                self.bit_depth,
+                self.color_type
+            );
+        }
+
+        if self.compression_method != 0 {
+            bail!("invalid compression method {}", self.compression_method);
+        }
+
+        if self.filter_method != 0 {
+            bail!("invalid filter method {}", self.filter_method);
+        }
+
+        Ok(())
+    }
+
+    pub fn to_chunk(&self) -> Chunk {
+        let mut data = Vec::with_capacity(Self::LENGTH);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.push(self.bit_depth);
+        data.push(self.color_type as u8);
+        data.push(self.compression_method);
+        data.push(self.filter_method);
+        data.push(self.interlace_method as u8);
+
+        let chunk_type = ChunkType::from_str(Self::CHUNK_TYPE).unwrap();
+
+        Chunk::new(chunk_type, data)
+    }
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = Error;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != Self::LENGTH {
+            bail!("IHDR data must be {} bytes long", Self::LENGTH);
+        }
+
+        let ihdr = Self {
+            width: u32::from_be_bytes(value[0..4].try_into()?),
+            height: u32::from_be_bytes(value[4..8].try_into()?),
+            bit_depth: value[8],
+            color_type: ColorType::try_from(value[9])?,
+            compression_method: value[10],
+            filter_method: value[11],
+            interlace_method: InterlaceMethod::try_from(value[12])?,
+        };
+
+        ihdr.validate()?;
+
+        Ok(ihdr)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    fn try_from(value: &Chunk) -> Result<Self, Self::Error> {
+        if value.chunk_type().to_string() != Self::CHUNK_TYPE {
+            bail!("expected an IHDR chunk, got {}", value.chunk_type());
+        }
+
+        Self::try_from(value.data())
+    }
+}
+
+impl Display for Ihdr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let interlace = match self.interlace_method {
+            InterlaceMethod::None => "None",
+            InterlaceMethod::Adam7 => "Adam7",
+        };
+
+        writeln!(f, "Width: {}", self.width)?;
+        writeln!(f, "Height: {}", self.height)?;
+        writeln!(f, "Bit depth: {}", self.bit_depth)?;
+        writeln!(f, "Color type: {}", self.color_type)?;
+        writeln!(f, "Compression method: {}", self.compression_method)?;
+        writeln!(f, "Filter method: {}", self.filter_method)?;
+        write!(f, "Interlace method: {interlace}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_ihdr() -> Ihdr {
+        Ihdr {
+            width: 91,
+            height: 69,
+            bit_depth: 8,
+            color_type: ColorType::Rgba,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let ihdr = testing_ihdr();
+        let chunk = ihdr.to_chunk();
+
+        assert_eq!(&chunk.chunk_type().to_string(), "IHDR");
+        assert_eq!(chunk.length(), 13);
+        assert_eq!(Ihdr::try_from(&chunk).unwrap(), ihdr);
+    }
+
+    #[test]
+    fn test_ihdr_from_bytes() {
+        #[rustfmt::skip]
+        let data = [
+            0, 0, 0, 91,    // width
+            0, 0, 0, 69,    // height
+            8, 6, 0, 0, 1,  // bit depth, color type, compression, filter, interlace
+        ];
+
+        let ihdr = Ihdr::try_from(&data[..]).unwrap();
+
+        assert_eq!(ihdr.width, 91);
+        assert_eq!(ihdr.height, 69);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.interlace_method, InterlaceMethod::Adam7);
+        assert_eq!(ihdr.bits_per_pixel(), 32);
+        assert_eq!(ihdr.bytes_per_row(), 364);
+    }
+
+    #[test]
+    fn test_invalid_bit_depth_for_color_type() {
+        let mut ihdr = testing_ihdr();
+        ihdr.bit_depth = 4;
+        assert!(ihdr.validate().is_err());
+
+        ihdr.color_type = ColorType::Indexed;
+        assert!(ihdr.validate().is_ok());
+
+        ihdr.bit_depth = 16;
+        assert!(ihdr.validate().is_err());
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        let mut ihdr = testing_ihdr();
+        ihdr.width = 0;
+        assert!(Ihdr::try_from(ihdr.to_chunk().data()).is_err());
+
+        let mut ihdr = testing_ihdr();
+        ihdr.filter_method = 1;
+        assert!(Ihdr::try_from(ihdr.to_chunk().data()).is_err());
+
+        let mut data = testing_ihdr().to_chunk().data().to_vec();
+        data[9] = 5;
+        assert!(Ihdr::try_from(&data[..]).is_err());
+
+        assert!(Ihdr::try_from(&data[..12]).is_err());
+    }
+
+    #[test]
+    fn test_sub_byte_row_length() {
+        let mut ihdr = testing_ihdr();
+        ihdr.color_type = ColorType::Grayscale;
+        ihdr.bit_depth = 1;
+        ihdr.width = 9;
+
+        assert_eq!(ihdr.bytes_per_row(), 2);
+    }
+}
@@ -1,5 +1,20 @@
+pub mod apng;
 pub mod chunk;
 pub mod chunk_type;
+pub mod envelope;
+pub mod exif;
+pub mod fragment;
+pub mod idat;
+pub mod ihdr;
+pub mod optimize;
 pub mod png;
+pub mod recovery;
+pub mod report;
+pub mod standard;
+pub mod stego;
+pub mod strip;
+pub mod text;
+pub mod validate;
+pub mod zlib;
 
 pub mod args;
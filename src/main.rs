@@ -1,10 +1,31 @@
-use std::fs;
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
 
 use anyhow::{bail, Result};
 use clap::Parser;
 use pngme::{
-    args::{self, Commands, PngArgs},
-    chunk::Chunk,
+    apng::{self, Animation, AnimationInfo},
+    args::{
+        self, ApngCommands, Commands, ExifCommands, Input, LsbCommands, OutputFormat, PngArgs,
+        SetField, TextCommands, TextKind,
+    },
+    chunk::{Chunk, ChunkRef},
+    envelope::{self, Message, SealOptions},
+    exif,
+    fragment::{self, Fragment, Reassembler},
+    ihdr::Ihdr,
+    optimize::{self, OptimizeOptions},
+    png::{Png, PngRef, Position},
+    report::{self, ChunkInfo, DecodeReport, MessageInfo, PrintReport},
+    standard::{self, Color, PhysicalDimensions, StandardChunk, Time},
+    stego,
+    strip::{self, ChunkFilter, StripOptions},
+    text::{self, TextChunk},
+    validate::Severity,
 };
 
 fn main() -> Result<()> {
@@ -15,66 +36,845 @@ fn main() -> Result<()> {
             input,
             chunk_type,
             message,
-            output_path,
+            position,
+            passphrase,
+            max_chunk_size,
+            compress,
+            file,
+            output,
+            ..
         } => {
-            let chunk = Chunk::new(chunk_type.clone(), message.as_bytes().to_vec());
-            let mut new_png = input.png.clone();
-            new_png.append_chunk(chunk);
+            let message = read_message(message.as_deref(), file.as_deref())?;
 
-            if let Some(output) = output_path {
-                fs::write(output, new_png.as_bytes())?;
-                return Ok(());
+            let options = SealOptions {
+                passphrase: passphrase.as_deref(),
+                compress: *compress,
+                ..Default::default()
             };
+            let data = envelope::seal(&message, &options)?;
 
-            if let Some(path) = &input.path {
-                fs::write(path, new_png.as_bytes())?;
+            let chunks = match data.len() <= *max_chunk_size {
+                true => vec![Chunk::try_new(chunk_type.clone(), data)?],
+                false => fragment::split(chunk_type, &data, *max_chunk_size)?,
+            };
+
+            let mut new_png = input.png()?;
+            let mut position = *position;
+            for chunk in chunks {
+                let index = new_png.insert_chunk(chunk, position)?;
+                position = Position::AfterChunk(index);
             }
 
-            Ok(())
+            write_output(&new_png, input, output.as_ref())
         }
 
-        args::Commands::Decode { input, chunk_type } => {
-            if let Some(chunk_type) = chunk_type {
-                let chunk = match input.png.chunk_by_type(&chunk_type.to_string()) {
-                    Some(c) => c,
-                    None => bail!("Chunk not found"),
+        args::Commands::Decode {
+            input,
+            chunk_type,
+            passphrase,
+            format,
+            output,
+            all,
+            index: wanted,
+        } => {
+            let passphrase = passphrase.as_deref();
+
+            let mut reassembler = Reassembler::new();
+            let mut messages = vec![];
+            let mut found = 0;
+            for (index, chunk) in input.chunks()?.enumerate() {
+                let chunk = chunk?;
+
+                if chunk_type.as_ref().is_some_and(|t| chunk.chunk_type() != t) {
+                    continue;
+                }
+
+                // split messages are only decoded once all fragments were read
+                let payload = match fragment::is_fragment(chunk.data()) {
+                    true => {
+                        let pushed = Fragment::parse(chunk.data())
+                            .and_then(|fragment| reassembler.push(index, fragment));
+                        match pushed {
+                            Ok(reassembled) => reassembled.map(|r| (r.chunk_index, r.message)),
+                            // anything can start with the magic when scanning every chunk
+                            Err(e) if chunk_type.is_none() => {
+                                eprintln!("Skipped fragment in chunk {index}: {e}");
+                                continue;
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    false => Some((index, chunk.data().to_vec())),
                 };
 
-                println!("{chunk}");
+                let Some((index, payload)) = payload else {
+                    continue;
+                };
 
-                return Ok(());
+                let chunk_type_name = chunk.chunk_type().to_string();
+
+                if chunk_type.is_some() {
+                    found += 1;
+                    if wanted.is_some_and(|wanted| wanted != found - 1) {
+                        continue;
+                    }
+
+                    let message = envelope::open(&payload, passphrase)?;
+
+                    if let Some(output) = output {
+                        return write_content(&message.content, output);
+                    }
+
+                    let text = match message.file {
+                        Some(_) => None,
+                        None => Some(String::from_utf8(message.content)?),
+                    };
+
+                    messages.push(MessageInfo {
+                        index,
+                        chunk_type: chunk_type_name,
+                        message: text,
+                        file: message.file,
+                    });
+
+                    // stops at the first match instead of reading the whole file
+                    if !all {
+                        break;
+                    }
+
+                    continue;
+                }
+
+                let Ok(message) = envelope::open(&payload, passphrase) else {
+                    continue;
+                };
+
+                let text = match message.file {
+                    Some(_) => None,
+                    None => match String::from_utf8(message.content) {
+                        Ok(text) if !text.is_empty() => Some(text),
+                        _ => continue,
+                    },
+                };
+
+                messages.push(MessageInfo {
+                    index,
+                    chunk_type: chunk_type_name,
+                    message: text,
+                    file: message.file,
+                });
+            }
+
+            for (received, expected) in reassembler.incomplete() {
+                match messages[..] {
+                    [] => bail!("Message incomplete, found {received} of {expected} fragments"),
+                    _ => eprintln!("Skipped message with {received} of {expected} fragments"),
+                }
+            }
+
+            match (&messages[..], chunk_type) {
+                ([], Some(chunk_type)) if wanted.is_some() => {
+                    bail!("Only {found} {chunk_type} chunk(s) found")
+                }
+                ([], Some(_)) => bail!("Chunk not found"),
+                ([], None) => bail!("No potential secret messages found."),
+                _ => {}
+            }
+
+            match format {
+                OutputFormat::Text => match chunk_type {
+                    Some(_) if *all => {
+                        for message in &messages {
+                            println!("{}: {}", message.index, message.summary());
+                        }
+                    }
+                    Some(_) => match &messages[0].file {
+                        Some(file) => println!("{file}, use --output to save it"),
+                        None => println!("{}", messages[0].summary()),
+                    },
+                    None => {
+                        let messages: Vec<_> = messages.iter().map(|m| m.summary()).collect();
+                        println!("{messages:?}");
+                    }
+                },
+                OutputFormat::Json => {
+                    let report = DecodeReport {
+                        schema_version: report::SCHEMA_VERSION,
+                        messages,
+                    };
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                OutputFormat::Table => println!("{}", report::messages_table(&messages)),
+            }
+
+            Ok(())
+        }
+
+        args::Commands::Remove {
+            input,
+            chunk_type,
+            all,
+            index,
+            contains,
+            min_size,
+            max_size,
+            output,
+        } => {
+            let mut new_png = input.png()?;
+
+            let filter = ChunkFilter {
+                pattern: chunk_type.clone(),
+                contains: contains.as_ref().map(|c| c.as_bytes().to_vec()),
+                min_size: *min_size,
+                max_size: *max_size,
+            };
+
+            // critical chunks are left to `--index`, but never silently
+            let skipped = match index {
+                Some(_) => 0,
+                None => new_png
+                    .chunks()
+                    .iter()
+                    .filter(|c| filter.matches(c) && !filter.removes(c))
+                    .count(),
             };
 
-            let messages: Vec<_> = input
-                .png
-                .chunks()
+            let index = match index {
+                Some(index) => Some(*index),
+                None => new_png.chunks().iter().position(|c| filter.removes(c)),
+            };
+
+            let removed = match (all, index) {
+                (true, _) => new_png.remove_where(|c| filter.removes(c)).len(),
+                (false, Some(index)) => new_png.remove_chunk_at(index).map(|_| 1)?,
+                (false, None) => 0,
+            };
+
+            if removed == 0 && skipped > 0 {
+                bail!("Only critical chunks match, remove them with `--index`");
+            }
+
+            if removed == 0 {
+                bail!("Chunk not found");
+            }
+
+            status(format!("Removed {removed} chunk(s)"), output.as_ref());
+            if *all && skipped > 0 {
+                status(
+                    format!("Skipped {skipped} critical chunk(s), remove them with `--index`"),
+                    output.as_ref(),
+                );
+            }
+
+            write_output(&new_png, input, output.as_ref())
+        }
+
+        args::Commands::Validate { input, strict } => {
+            let violations = input.png()?.validate();
+
+            for violation in &violations {
+                println!("{violation}");
+            }
+
+            let failed = violations
                 .iter()
-                .filter_map(|c| c.data_as_string().ok())
-                .filter(|s| !s.is_empty())
-                .collect();
+                .filter(|v| *strict || v.severity == Severity::Error)
+                .count();
 
-            match messages[..] {
-                [] => bail!("No potential secret messages found."),
-                _ => println!("{messages:?}"),
+            match (violations.len(), failed) {
+                (0, _) => println!("No violations found."),
+                (_, 0) => {}
+                (_, failed) => bail!("{failed} violation(s) found"),
             }
 
             Ok(())
         }
 
-        args::Commands::Remove { input, chunk_type } => {
-            let new_png = input.png.clone().remove_chunk(&chunk_type.to_string())?;
+        args::Commands::Repair {
+            input,
+            output,
+            dry_run,
+        } => {
+            let recovery = Png::recover(&input.bytes()?)?;
+
+            if recovery.issues.is_empty() {
+                println!("No problems found.");
+                return Ok(());
+            }
+
+            for issue in &recovery.issues {
+                status(issue, output.as_ref());
+            }
 
-            if let Some(path) = &input.path {
-                fs::write(path, new_png.as_bytes())?;
+            if *dry_run {
+                return Ok(());
             }
 
+            write_output(&recovery.png, input, output.as_ref())
+        }
+
+        args::Commands::Strip {
+            input,
+            output,
+            keep,
+            drop,
+            dry_run,
+        } => {
+            let options = StripOptions {
+                keep: keep.clone(),
+                drop: drop.clone(),
+            };
+
+            let mut new_png = input.png()?;
+            let removed = strip::strip(&mut new_png, &options);
+
+            if *dry_run {
+                for chunk in &removed {
+                    println!(
+                        "Would remove {} ({} bytes)",
+                        chunk.chunk_type(),
+                        chunk.length()
+                    );
+                }
+                println!("{} chunk(s) would be removed", removed.len());
+                return Ok(());
+            }
+
+            for chunk in &removed {
+                let line = format!("Removed {} ({} bytes)", chunk.chunk_type(), chunk.length());
+                status(line, output.as_ref());
+            }
+            status(
+                format!("{} chunk(s) removed", removed.len()),
+                output.as_ref(),
+            );
+
+            write_output(&new_png, input, output.as_ref())
+        }
+
+        args::Commands::Optimize {
+            input,
+            output,
+            level,
+            idat_size,
+            dry_run,
+        } => {
+            let options = OptimizeOptions {
+                level: *level,
+                chunk_size: *idat_size,
+            };
+
+            let mut new_png = input.png()?;
+            let optimization = optimize::optimize(&mut new_png, &options)?;
+            status(optimization, output.as_ref());
+
+            if *dry_run {
+                return Ok(());
+            }
+
+            write_output(&new_png, input, output.as_ref())
+        }
+
+        args::Commands::Text { command } => run_text(command),
+
+        args::Commands::Lsb { command } => run_lsb(command),
+
+        args::Commands::Apng { command } => run_apng(command),
+
+        args::Commands::Exif { command } => run_exif(command),
+
+        args::Commands::Inspect { input } => {
+            let png = input.png()?;
+            println!("{}\n", png.ihdr()?);
+
+            let chunks = standard::list(&png)?;
+            if chunks.is_empty() {
+                println!("No standard chunks found.");
+            }
+
+            for (index, chunk) in chunks {
+                match chunk {
+                    Ok(chunk) => println!("{index} {}: {chunk}", chunk.chunk_type()),
+                    Err(e) => {
+                        println!("{index} {}: invalid: {e}", png.chunks()[index].chunk_type())
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        args::Commands::Set {
+            input,
+            field,
+            value,
+            output,
+        } => {
+            let mut png = input.png()?;
+
+            let line = match value.as_str() {
+                "none" => {
+                    let removed = png.remove_all(field.chunk_type()).len();
+                    format!("Removed {removed} {} chunk(s)", field.chunk_type())
+                }
+                value => {
+                    let chunk = standard_chunk(*field, value, &png.ihdr()?)?;
+                    let index = standard::set(&mut png, &chunk)?;
+                    format!("{index} {}: {chunk}", chunk.chunk_type())
+                }
+            };
+
+            write_output(&png, input, output.as_ref())?;
+            status(line, output.as_ref());
+
             Ok(())
         }
 
-        args::Commands::Print { input } => {
-            let file = &input.png;
-            print!("{file}");
+        args::Commands::Print {
+            input,
+            format,
+            preview,
+            long,
+        } => {
+            let (ihdr, chunks, animation_chunks) = match long {
+                true => read_stored_chunk_infos(input, *preview)?,
+                false => read_chunk_infos(input, *preview)?,
+            };
+            let animation = AnimationInfo::from_chunks(&animation_chunks);
+
+            match format {
+                OutputFormat::Text => {
+                    if let Some(ihdr) = ihdr {
+                        println!("{ihdr}\n");
+                    }
+
+                    match animation {
+                        Ok(Some(animation)) => println!("{animation}\n"),
+                        Ok(None) => {}
+                        Err(e) => println!("Animation: invalid: {e}\n"),
+                    }
+
+                    if *long {
+                        println!("{}", report::long_listing(&chunks));
+                    } else {
+                        let chunk_types: Vec<_> = chunks.iter().map(|c| &c.chunk_type).collect();
+                        print!("{chunk_types:#?}");
+                    }
+                }
+                OutputFormat::Json => {
+                    let (animation, animation_error) = match animation {
+                        Ok(animation) => (animation, None),
+                        Err(e) => (None, Some(e.to_string())),
+                    };
+                    let report = PrintReport {
+                        schema_version: report::SCHEMA_VERSION,
+                        ihdr,
+                        chunks,
+                        animation,
+                        animation_error,
+                    };
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                }
+                OutputFormat::Table => println!("{}", report::chunks_table(&chunks)),
+            }
+
             Ok(())
         }
     }
 }
+
+/// Infos of every chunk, along with the chunks [`AnimationInfo::from_chunks`] needs
+type ChunkInfos = (Option<Ihdr>, Vec<ChunkInfo>, Vec<Chunk>);
+
+/// Streams the chunks, only their infos are kept and the data is dropped right after reading
+fn read_chunk_infos(input: &Input, preview: Option<usize>) -> Result<ChunkInfos> {
+    let mut ihdr = None;
+    let mut chunks = vec![];
+    let mut animation_chunks = vec![];
+    let mut offset = Png::STANDARD_HEADER.len();
+
+    for (index, chunk) in input.chunks()?.enumerate() {
+        let chunk = chunk?;
+        if index == 0 {
+            ihdr = Ihdr::try_from(&chunk).ok();
+        }
+
+        let mut info = ChunkInfo::new(index, offset, &chunk);
+        if let Some(length) = preview {
+            info = info.with_preview(chunk.data(), length);
+        }
+
+        offset += chunk.data().len() + ChunkRef::OVERHEAD;
+        chunks.push(info);
+
+        match chunk.chunk_type().to_string().as_str() {
+            apng::ACTL | apng::FCTL => animation_chunks.push(chunk),
+            "IDAT" => animation_chunks.push(Chunk::new(chunk.chunk_type().clone(), vec![])),
+            _ => {}
+        }
+    }
+
+    Ok((ihdr, chunks, animation_chunks))
+}
+
+/// Reads the chunks as stored in the file, without rejecting crc mismatches
+fn read_stored_chunk_infos(input: &Input, preview: Option<usize>) -> Result<ChunkInfos> {
+    let bytes = input.bytes()?;
+    let png = PngRef::new(&bytes)?;
+
+    let mut ihdr = None;
+    let mut chunks = vec![];
+    let mut animation_chunks = vec![];
+
+    for (index, chunk) in png.chunks().enumerate() {
+        let chunk = chunk?;
+        if index == 0 && chunk.chunk_type_bytes() == *b"IHDR" {
+            ihdr = Ihdr::try_from(chunk.data()).ok();
+        }
+
+        let mut info = ChunkInfo::from_ref(index, &chunk)?;
+        if let Some(length) = preview {
+            info = info.with_preview(chunk.data(), length);
+        }
+
+        chunks.push(info);
+
+        match &chunk.chunk_type_bytes() {
+            b"acTL" | b"fcTL" => {
+                animation_chunks.push(Chunk::try_new(chunk.chunk_type()?, chunk.data().to_vec())?)
+            }
+            b"IDAT" => animation_chunks.push(Chunk::new(chunk.chunk_type()?, vec![])),
+            _ => {}
+        }
+    }
+
+    Ok((ihdr, chunks, animation_chunks))
+}
+
+/// Builds the chunk for a `set` field from its command line value
+fn standard_chunk(field: SetField, value: &str, ihdr: &Ihdr) -> Result<StandardChunk> {
+    let decimal = |s: &str| match s.parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(value),
+        _ => bail!("Expected a positive number, not `{s}`"),
+    };
+
+    Ok(match field {
+        SetField::Dpi => {
+            let (x, y) = value.split_once('x').unwrap_or((value, value));
+            StandardChunk::PhysicalDimensions(PhysicalDimensions::from_dpi(
+                decimal(x)?,
+                decimal(y)?,
+            )?)
+        }
+        SetField::Gamma => StandardChunk::Gamma(standard::fixed_point(value)?),
+        SetField::Srgb => StandardChunk::Srgb(value.parse()?),
+        SetField::Chrm => StandardChunk::Chromaticities(value.parse()?),
+        SetField::Time => StandardChunk::Time(match value {
+            "now" => Time::now()?,
+            value => value.parse()?,
+        }),
+        SetField::Background => {
+            StandardChunk::Background(Color::parse_samples(value, ihdr.color_type)?)
+        }
+    })
+}
+
+fn run_text(command: &TextCommands) -> Result<()> {
+    match command {
+        TextCommands::List { input } => {
+            let texts = text::list(&input.png()?);
+
+            if texts.is_empty() {
+                bail!("No text chunks found.");
+            }
+
+            for (index, text) in texts {
+                match text {
+                    Ok(text) => println!("{index} {}: {text}", text.chunk_type()),
+                    Err(e) => println!("{index} invalid text chunk: {e}"),
+                }
+            }
+
+            Ok(())
+        }
+
+        TextCommands::Get { input, keyword } => {
+            let texts = text::get(&input.png()?, keyword);
+
+            if texts.is_empty() {
+                bail!("Keyword not found");
+            }
+
+            for text in texts {
+                println!("{}", text.text());
+            }
+
+            Ok(())
+        }
+
+        TextCommands::Set {
+            input,
+            keyword,
+            text,
+            output,
+            kind,
+            language,
+            translated_keyword,
+            compress,
+        } => {
+            let (keyword, text) = (keyword.clone(), text.clone());
+            let text = match kind {
+                TextKind::Text => TextChunk::Text { keyword, text },
+                TextKind::Ztxt => TextChunk::CompressedText { keyword, text },
+                TextKind::Itxt => TextChunk::InternationalText {
+                    keyword,
+                    compressed: *compress,
+                    language_tag: language.clone(),
+                    translated_keyword: translated_keyword.clone(),
+                    text,
+                },
+            };
+
+            let mut new_png = input.png()?;
+            text::set(&mut new_png, &text)?;
+
+            write_output(&new_png, input, output.as_ref())
+        }
+
+        TextCommands::Delete {
+            input,
+            keyword,
+            output,
+        } => {
+            let mut new_png = input.png()?;
+
+            if text::delete(&mut new_png, keyword)? == 0 {
+                bail!("Keyword not found");
+            }
+
+            write_output(&new_png, input, output.as_ref())
+        }
+    }
+}
+
+fn run_lsb(command: &LsbCommands) -> Result<()> {
+    match command {
+        LsbCommands::Capacity { input, lsb } => {
+            let capacity = lsb.options().capacity(&input.png()?.ihdr()?)?;
+            println!(
+                "{capacity} bytes, using {} bit(s) of the {} channels",
+                lsb.bits, lsb.channels
+            );
+
+            Ok(())
+        }
+
+        LsbCommands::Encode {
+            input,
+            message,
+            file,
+            output,
+            passphrase,
+            compress,
+            lsb,
+            ..
+        } => {
+            let message = read_message(message.as_deref(), file.as_deref())?;
+
+            let options = SealOptions {
+                passphrase: passphrase.as_deref(),
+                compress: *compress,
+                ..Default::default()
+            };
+            let data = envelope::seal(&message, &options)?;
+
+            let mut new_png = input.png()?;
+            stego::embed(&mut new_png, &data, &lsb.options())?;
+
+            write_output(&new_png, input, output.as_ref())
+        }
+
+        LsbCommands::Decode {
+            input,
+            passphrase,
+            output,
+            lsb,
+        } => {
+            let data = stego::extract(&input.png()?, &lsb.options())?;
+            let message = envelope::open(&data, passphrase.as_deref())?;
+
+            if let Some(output) = output {
+                return write_content(&message.content, output);
+            }
+
+            match message.file {
+                Some(file) => println!("{file}, use --output to save it"),
+                None => println!("{}", String::from_utf8(message.content)?),
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn run_apng(command: &ApngCommands) -> Result<()> {
+    match command {
+        ApngCommands::Split {
+            input,
+            output_dir,
+            raw,
+        } => {
+            let animation = Animation::read(&input.png()?)?;
+            let frames = match raw {
+                true => (0..animation.frames.len())
+                    .map(|index| animation.frame_png(index))
+                    .collect::<Result<Vec<_>>>()?,
+                false => animation.composite()?,
+            };
+
+            fs::create_dir_all(output_dir)?;
+            for (index, frame) in frames.iter().enumerate() {
+                write_png(frame, output_dir.join(format!("frame-{index:03}.png")))?;
+            }
+
+            println!(
+                "Wrote {} frame(s) to {}",
+                frames.len(),
+                output_dir.display()
+            );
+
+            Ok(())
+        }
+
+        ApngCommands::Join {
+            inputs,
+            output,
+            delay,
+            plays,
+        } => {
+            let frames = inputs.iter().map(Input::png).collect::<Result<Vec<_>>>()?;
+
+            let delays = match delay.as_slice() {
+                &[delay] => vec![delay; frames.len()],
+                delays => delays.to_vec(),
+            };
+
+            let png = apng::join(&frames, &delays, *plays)?;
+            write_png(&png, output)?;
+            status(format!("Joined {} frame(s)", frames.len()), Some(output));
+
+            Ok(())
+        }
+    }
+}
+
+fn run_exif(command: &ExifCommands) -> Result<()> {
+    match command {
+        ExifCommands::Show { input } => {
+            let exif = exif::read(&input.png()?)?;
+
+            let summary = exif.summary();
+            for (label, value) in &summary {
+                println!("{label}: {value}");
+            }
+            if !summary.is_empty() {
+                println!();
+            }
+
+            println!("{exif}");
+
+            Ok(())
+        }
+
+        ExifCommands::StripGps { input, output } => {
+            let mut png = input.png()?;
+            let mut exif = exif::read(&png)?;
+
+            if !exif.strip_gps() {
+                bail!("No GPS tags found");
+            }
+            exif::write(&mut png, &exif)?;
+
+            write_output(&png, input, output.as_ref())
+        }
+
+        ExifCommands::RemoveTag {
+            input,
+            tags,
+            output,
+        } => {
+            let mut png = input.png()?;
+            let mut exif = exif::read(&png)?;
+
+            for &tag in tags {
+                if exif.remove_tag(tag) == 0 {
+                    bail!("Tag {tag} not found");
+                }
+            }
+            exif::write(&mut png, &exif)?;
+
+            write_output(&png, input, output.as_ref())
+        }
+    }
+}
+
+/// The message given on the command line, or else the content of `file` or stdin
+fn read_message(message: Option<&str>, file: Option<&Path>) -> Result<Message> {
+    Ok(match (message, file) {
+        (Some(message), _) => Message::text(message),
+        (None, Some(path)) => {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            Message::file(&name, fs::read(path)?)
+        }
+        (None, None) => {
+            let mut content = Vec::new();
+            io::stdin().read_to_end(&mut content)?;
+            Message::file("", content)
+        }
+    })
+}
+
+/// Writes decoded content to a file, or to stdout for `-`
+fn write_content(content: &[u8], output: &Path) -> Result<()> {
+    if output == Path::new("-") {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(content)?;
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    fs::write(output, content)?;
+
+    Ok(())
+}
+
+/// Writes to `output_path` if given, otherwise back to the input file
+fn write_output(png: &Png, input: &Input, output_path: Option<&PathBuf>) -> Result<()> {
+    match (output_path, &input.path) {
+        (Some(output), _) => write_png(png, output),
+        (None, Some(path)) => write_png(png, path),
+        (None, None) => {
+            bail!("Nowhere to write the PNG for a url input, give an output path or `-` for stdout")
+        }
+    }
+}
+
+/// Writes the PNG to a file, or to stdout for `-`
+fn write_png(png: &Png, path: impl AsRef<Path>) -> Result<()> {
+    if path.as_ref() == Path::new("-") {
+        png.write_to(BufWriter::new(io::stdout().lock()))?;
+        return Ok(());
+    }
+
+    png.write_to(BufWriter::new(File::create(path)?))?;
+
+    Ok(())
+}
+
+/// Prints a status line, to stderr if the PNG itself goes to stdout
+fn status(line: impl Display, output_path: Option<&PathBuf>) {
+    match output_path.is_some_and(|p| p == Path::new("-")) {
+        true => eprintln!("{line}"),
+        false => println!("{line}"),
+    }
+}
@@ -0,0 +1,258 @@
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    idat::{self, FilterStrategy, FilterType, Scanlines},
+    png::Png,
+};
+
+/// How hard to try when optimizing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OptimizeOptions {
+    /// Highest zlib compression level tried, 0 to 9
+    pub level: u32,
+    /// Data size of the written `IDAT` chunks
+    pub chunk_size: usize,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            level: 9,
+            chunk_size: idat::DEFAULT_CHUNK_SIZE,
+        }
+    }
+}
+
+/// What optimizing changed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Optimization {
+    /// The filters the rows use now, `None` if the original image data was kept
+    pub strategy: Option<FilterStrategy>,
+    /// The compression level giving the smallest data
+    pub level: u32,
+    pub original_data_size: usize,
+    pub data_size: usize,
+    pub original_chunks: usize,
+    pub chunks: usize,
+    pub original_file_size: usize,
+    pub file_size: usize,
+}
+
+impl Optimization {
+    /// Bytes saved on the whole file, negative if it grew
+    pub fn saved(&self) -> i64 {
+        self.original_file_size as i64 - self.file_size as i64
+    }
+}
+
+impl Display for Optimization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.strategy {
+            Some(strategy) => writeln!(f, "Filters: {strategy}, level {}", self.level)?,
+            None => writeln!(f, "Filters: kept, the original image data was smaller")?,
+        }
+
+        writeln!(
+            f,
+            "IDAT: {} chunk(s), {} bytes -> {} chunk(s), {} bytes",
+            self.original_chunks, self.original_data_size, self.chunks, self.data_size
+        )?;
+
+        let percent = match self.original_file_size {
+            0 => 0.0,
+            size => self.saved() as f64 * 100.0 / size as f64,
+        };
+        write!(
+            f,
+            "File: {} -> {} bytes, saved {} bytes ({percent:.1}%)",
+            self.original_file_size,
+            self.file_size,
+            self.saved()
+        )
+    }
+}
+
+/// Recompresses the image data of `png` with whichever filters and level give the smallest result
+///
+/// Every fixed filter and the adaptive strategy are tried at every level up to
+/// `options.level`, the original data is kept if none of them is smaller. The image data is split into chunks of
+/// `options.chunk_size` either way, all other chunks stay untouched.
+pub fn optimize(png: &mut Png, options: &OptimizeOptions) -> Result<Optimization> {
+    if options.level > 9 {
+        bail!(
+            "compression level must be between 0 and 9, not {}",
+            options.level
+        );
+    }
+
+    let original = idat::image_data(png);
+    let original_chunks = idat_chunks(png);
+    let original_file_size = png.as_bytes().len();
+
+    let mut scanlines = Scanlines::read(png)?;
+
+    let mut best: Option<(FilterStrategy, u32, Vec<u8>)> = None;
+    let strategies = FilterType::ALL
+        .into_iter()
+        .map(FilterStrategy::Fixed)
+        .chain([FilterStrategy::Adaptive]);
+
+    for strategy in strategies {
+        scanlines.refilter(strategy);
+
+        for level in 0..=options.level {
+            let data = scanlines.to_zlib(level)?;
+
+            if best
+                .as_ref()
+                .is_none_or(|(_, _, best)| data.len() < best.len())
+            {
+                best = Some((strategy, level, data));
+            }
+        }
+    }
+
+    let (strategy, level, data) = match best {
+        Some((strategy, level, data)) if data.len() < original.len() => {
+            (Some(strategy), level, data)
+        }
+        _ => (None, options.level, original.clone()),
+    };
+
+    idat::replace_image_data(png, &data, options.chunk_size)?;
+
+    Ok(Optimization {
+        strategy,
+        level,
+        original_data_size: original.len(),
+        data_size: data.len(),
+        original_chunks,
+        chunks: idat_chunks(png),
+        original_file_size,
+        file_size: png.as_bytes().len(),
+    })
+}
+
+fn idat_chunks(png: &Png) -> usize {
+    png.chunks()
+        .iter()
+        .filter(|c| c.chunk_type().to_string() == idat::CHUNK_TYPE)
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::Chunk,
+        idat::RawImage,
+        ihdr::{ColorType, Ihdr, InterlaceMethod},
+    };
+
+    /// A gradient stored without filters and compression, split over two chunks
+    fn testing_png() -> Png {
+        let ihdr = Ihdr {
+            width: 32,
+            height: 32,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        };
+        let pixels = (0..32 * 32 * 3).map(|i| (i % 96 + i / 96) as u8).collect();
+        let image = RawImage { ihdr, pixels };
+        let data = image
+            .encode(FilterStrategy::Fixed(FilterType::None), 0)
+            .unwrap();
+        let (first, second) = data.split_at(data.len() / 2);
+
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new("IDAT".parse().unwrap(), first.to_vec()),
+            Chunk::new("ruSt".parse().unwrap(), b"hidden".to_vec()),
+            Chunk::new("IDAT".parse().unwrap(), second.to_vec()),
+            Chunk::new("IEND".parse().unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_optimize() {
+        let mut png = testing_png();
+        let image = RawImage::decode(&png).unwrap();
+
+        let optimization = optimize(&mut png, &OptimizeOptions::default()).unwrap();
+
+        assert!(optimization.strategy.is_some());
+        assert!(optimization.data_size < optimization.original_data_size);
+        assert!(optimization.saved() > 0);
+        assert_eq!(optimization.file_size, png.as_bytes().len());
+        assert_eq!((optimization.original_chunks, optimization.chunks), (2, 1));
+
+        assert_eq!(RawImage::decode(&png).unwrap(), image);
+        assert_eq!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden");
+    }
+
+    #[test]
+    fn test_optimize_searches_levels() {
+        let mut png = testing_png();
+        let scanlines = Scanlines::read(&png).unwrap();
+        let options = OptimizeOptions {
+            level: 4,
+            ..Default::default()
+        };
+
+        let optimization = optimize(&mut png, &options).unwrap();
+        assert!(optimization.level <= 4);
+
+        // no filter is smaller at any level up to the maximum
+        for strategy in FilterType::ALL.map(FilterStrategy::Fixed) {
+            let mut scanlines = scanlines.clone();
+            scanlines.refilter(strategy);
+            for level in 0..=4 {
+                let size = scanlines.to_zlib(level).unwrap().len();
+                assert!(optimization.data_size <= size, "{strategy} level {level}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_optimize_splits_chunks() {
+        let mut png = testing_png();
+        let options = OptimizeOptions {
+            level: 9,
+            chunk_size: 16,
+        };
+
+        let optimization = optimize(&mut png, &options).unwrap();
+        assert_eq!(optimization.chunks, optimization.data_size.div_ceil(16));
+    }
+
+    #[test]
+    fn test_optimize_keeps_smaller_data() {
+        let mut png = testing_png();
+        optimize(&mut png, &OptimizeOptions::default()).unwrap();
+        let data = idat::image_data(&png);
+
+        let options = OptimizeOptions {
+            level: 0,
+            ..Default::default()
+        };
+        let optimization = optimize(&mut png, &options).unwrap();
+
+        assert_eq!(optimization.strategy, None);
+        assert_eq!(optimization.saved(), 0);
+        assert_eq!(idat::image_data(&png), data);
+    }
+
+    #[test]
+    fn test_optimize_invalid_level() {
+        let options = OptimizeOptions {
+            level: 10,
+            ..Default::default()
+        };
+        assert!(optimize(&mut testing_png(), &options).is_err());
+    }
+}
@@ -1,10 +1,49 @@
 use std::{
     fmt::Display,
-    io::{BufReader, Read},
+    io::{BufRead, BufReader, Read, Write},
+    str::FromStr,
 };
 
-use crate::chunk::Chunk;
-use anyhow::{bail, Error, Result};
+use crate::{
+    chunk::{Chunk, ChunkRef},
+    ihdr::Ihdr,
+    recovery::{self, Recovery},
+    report::ChunkInfo,
+    validate::{self, Violation},
+};
+use anyhow::{anyhow, bail, Error, Result};
+
+/// Where a new chunk is placed inside a [`Png`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Position {
+    /// Right before the `IEND` chunk, or at the very end if there is none
+    #[default]
+    BeforeIend,
+    /// Right after the `IHDR` chunk
+    AfterIhdr,
+    /// Right before the first `IDAT` chunk
+    BeforeIdat,
+    /// Right after the chunk with the given index
+    AfterChunk(usize),
+}
+
+impl FromStr for Position {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "before-iend" => Ok(Position::BeforeIend),
+            "after-ihdr" => Ok(Position::AfterIhdr),
+            "before-idat" => Ok(Position::BeforeIdat),
+            _ => match s.strip_prefix("after-") {
+                Some(index) => Ok(Position::AfterChunk(index.parse()?)),
+                None => bail!(
+                    "position has to be one of `before-iend`, `after-ihdr`, `before-idat` or `after-<index>`"
+                ),
+            },
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Png {
@@ -12,7 +51,7 @@ pub struct Png {
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+    pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
         Self { chunks }
@@ -22,12 +61,39 @@ impl Png {
         self.chunks.push(chunk);
     }
 
-    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, Error> {
-        let index = match self
-            .chunks
+    /// Inserts `chunk` at `position` and returns the index it ended up at
+    pub fn insert_chunk(&mut self, chunk: Chunk, position: Position) -> Result<usize> {
+        let index = match position {
+            Position::BeforeIend => self.position_of("IEND").unwrap_or(self.chunks.len()),
+            Position::AfterIhdr => {
+                self.position_of("IHDR")
+                    .ok_or_else(|| anyhow!("IHDR chunk not found"))?
+                    + 1
+            }
+            Position::BeforeIdat => self
+                .position_of("IDAT")
+                .ok_or_else(|| anyhow!("IDAT chunk not found"))?,
+            Position::AfterChunk(index) => {
+                if index >= self.chunks.len() {
+                    bail!("Chunk index {index} out of range");
+                }
+                index + 1
+            }
+        };
+
+        self.chunks.insert(index, chunk);
+
+        Ok(index)
+    }
+
+    fn position_of(&self, chunk_type: &str) -> Option<usize> {
+        self.chunks
             .iter()
             .position(|c| c.chunk_type().to_string() == chunk_type)
-        {
+    }
+
+    pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk, Error> {
+        let index = match self.position_of(chunk_type) {
             Some(c) => c,
             None => bail!("Chunk not found!"),
         };
@@ -35,6 +101,42 @@ impl Png {
         Ok(self.chunks.remove(index))
     }
 
+    pub fn remove_chunk_at(&mut self, index: usize) -> Result<Chunk> {
+        if index >= self.chunks.len() {
+            bail!("Chunk index {index} out of range");
+        }
+
+        Ok(self.chunks.remove(index))
+    }
+
+    /// Removes every chunk of `chunk_type` and returns them
+    pub fn remove_all(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.remove_where(|c| c.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Removes every chunk `predicate` returns true for and returns them
+    pub fn remove_where<F: FnMut(&Chunk) -> bool>(&mut self, mut predicate: F) -> Vec<Chunk> {
+        self.retain(|c| !predicate(c))
+    }
+
+    /// Keeps only the chunks `keep` returns true for and returns the removed ones
+    pub fn retain<F: FnMut(&Chunk) -> bool>(&mut self, mut keep: F) -> Vec<Chunk> {
+        let (kept, removed) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|c| keep(c));
+        self.chunks = kept;
+
+        removed
+    }
+
+    /// Puts `chunk` in place of the chunk at `index` and returns the old one
+    pub fn replace_chunk_at(&mut self, index: usize, chunk: Chunk) -> Result<Chunk> {
+        match self.chunks.get_mut(index) {
+            Some(old) => Ok(std::mem::replace(old, chunk)),
+            None => bail!("Chunk index {index} out of range"),
+        }
+    }
+
     pub fn header(&self) -> &[u8; 8] {
         &Self::STANDARD_HEADER
     }
@@ -44,9 +146,44 @@ impl Png {
     }
 
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
+        self.chunks_by_type(chunk_type).next()
+    }
+
+    /// Every chunk of `chunk_type`, in order
+    pub fn chunks_by_type(&self, chunk_type: &str) -> impl Iterator<Item = &Chunk> {
+        let chunk_type = chunk_type.to_string();
+
         self.chunks
             .iter()
-            .find(|c| c.chunk_type().to_string() == chunk_type)
+            .filter(move |c| c.chunk_type().to_string() == chunk_type)
+    }
+
+    /// Decodes the `IHDR` chunk, which has to be the first chunk
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        match self.chunks.first() {
+            Some(chunk) => Ihdr::try_from(chunk),
+            None => bail!("PNG has no chunks"),
+        }
+    }
+
+    /// Describes every chunk, including its offset once written
+    pub fn chunk_infos(&self) -> Vec<ChunkInfo> {
+        let mut offset = Self::STANDARD_HEADER.len();
+
+        self.chunks
+            .iter()
+            .enumerate()
+            .map(|(index, chunk)| {
+                let info = ChunkInfo::new(index, offset, chunk);
+                offset += chunk.data().len() + ChunkRef::OVERHEAD;
+                info
+            })
+            .collect()
+    }
+
+    /// Checks the chunk layout against the PNG spec
+    pub fn validate(&self) -> Vec<Violation> {
+        validate::validate(self)
     }
 
     pub fn as_bytes(&self) -> Vec<u8> {
@@ -58,44 +195,192 @@ impl Png {
 
         result.to_vec()
     }
+
+    /// Parses a possibly damaged PNG, recording problems instead of bailing
+    pub fn recover(bytes: &[u8]) -> Result<Recovery> {
+        recovery::parse_lenient(bytes)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self> {
+        let chunks = ChunkReader::new(reader)?.collect::<Result<Vec<_>>>()?;
+
+        Ok(Self::from_chunks(chunks))
+    }
+
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<W> {
+        let mut writer = ChunkWriter::new(writer)?;
+
+        for chunk in &self.chunks {
+            writer.write_chunk(chunk)?;
+        }
+
+        writer.finish()
+    }
 }
 
 impl TryFrom<&[u8]> for Png {
     type Error = Error;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
-        let mut reader = BufReader::new(value);
+        Self::from_reader(value)
+    }
+}
+
+/// A PNG borrowed from a byte slice, e.g. a whole file or a memory map
+#[derive(Clone, Copy, Debug)]
+pub struct PngRef<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> PngRef<'a> {
+    /// Checks the PNG header, chunks are only parsed while iterating
+    pub fn new(bytes: &'a [u8]) -> Result<Self> {
+        if !bytes.starts_with(&Png::STANDARD_HEADER) {
+            bail!("invalid header")
+        }
+
+        Ok(Self { bytes })
+    }
+
+    pub fn chunks(&self) -> ChunkRefs<'a> {
+        ChunkRefs {
+            rest: &self.bytes[Png::STANDARD_HEADER.len()..],
+            offset: Png::STANDARD_HEADER.len(),
+        }
+    }
+
+    /// Describes every chunk as stored, including crcs that don't match
+    pub fn chunk_infos(&self) -> Result<Vec<ChunkInfo>> {
+        self.chunks()
+            .enumerate()
+            .map(|(index, chunk)| ChunkInfo::from_ref(index, &chunk?))
+            .collect()
+    }
+
+    /// Copies all chunks into an owned [`Png`], checking their types and crcs
+    pub fn to_png(&self) -> Result<Png> {
+        let chunks = self
+            .chunks()
+            .map(|c| c.and_then(|c| c.to_chunk()))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Png::from_chunks(chunks))
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for PngRef<'a> {
+    type Error = Error;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+/// Iterator over the chunks of a [`PngRef`]
+#[derive(Clone, Debug)]
+pub struct ChunkRefs<'a> {
+    rest: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+
+        match ChunkRef::parse_at(self.rest, self.offset) {
+            Ok((chunk, rest)) => {
+                self.offset += chunk.as_bytes().len();
+                self.rest = rest;
+                Some(Ok(chunk))
+            }
+            Err(e) => {
+                self.rest = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Lazily reads the chunks of a PNG stream, one chunk at a time
+pub struct ChunkReader<R: Read> {
+    reader: BufReader<R>,
+    done: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Reads and checks the PNG header, leaving the chunks unread
+    pub fn new(reader: R) -> Result<Self> {
+        let mut reader = BufReader::new(reader);
         let mut header: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
 
         if reader.read_exact(&mut header).is_err() {
             bail!("Failed reading header bytes")
         };
 
-        if header != Self::STANDARD_HEADER {
+        if header != Png::STANDARD_HEADER {
             bail!("invalid header")
         }
 
-        let mut chunks: Vec<Chunk> = vec![];
-        loop {
-            let mut data_length: [u8; 4] = [0, 0, 0, 0];
-            if reader.read_exact(&mut data_length).is_err() {
-                // if !reader.fill_buf()?.is_empty() {
-                //     bail!("invalid bytes");
-                // }
-                break Ok(Self::from_chunks(chunks));
-            };
-
-            let data_length_u32 = u32::from_be_bytes(data_length);
+        Ok(Self {
+            reader,
+            done: false,
+        })
+    }
+}
 
-            let mut buffer = vec![0u8; (data_length_u32 + 8).try_into()?];
-            if reader.read_exact(&mut buffer).is_err() {
-                bail!("Failed reading chunk bytes",)
-            };
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<Chunk>;
 
-            let buffer: Vec<u8> = data_length.iter().chain(buffer.iter()).cloned().collect();
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
 
-            chunks.push(Chunk::try_from(&buffer[..])?);
+        match self.reader.fill_buf() {
+            Ok([]) => {
+                self.done = true;
+                return None;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e.into()));
+            }
         }
+
+        let chunk = Chunk::read_from(&mut self.reader);
+        self.done = chunk.is_err();
+
+        Some(chunk)
+    }
+}
+
+/// Writes a PNG stream chunk by chunk
+pub struct ChunkWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Writes the PNG header
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&Png::STANDARD_HEADER)?;
+
+        Ok(Self { writer })
+    }
+
+    pub fn write_chunk(&mut self, chunk: &Chunk) -> Result<()> {
+        chunk.write_to(&mut self.writer)
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+
+        Ok(self.writer)
     }
 }
 
@@ -111,21 +396,19 @@ impl Display for Png {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use crate::chunk::Chunk;
+    use crate::chunk::{Chunk, ChunkRef};
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
     use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
-
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
-
-        chunks
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -243,6 +526,191 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_insert_chunk_before_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunk_count = png.chunks().len();
+        let index = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                Position::BeforeIend,
+            )
+            .unwrap();
+
+        assert_eq!(index, chunk_count - 1);
+        assert_eq!(&png.chunks()[index].chunk_type().to_string(), "TeSt");
+        assert_eq!(
+            &png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+
+        let png = Png::try_from(&png.as_bytes()[..]).unwrap();
+        assert_eq!(&png.chunks()[index].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_insert_chunk_without_iend() {
+        let mut png = testing_png();
+        let index = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                Position::BeforeIend,
+            )
+            .unwrap();
+        assert_eq!(index, 3);
+    }
+
+    #[test]
+    fn test_insert_chunk_after_ihdr() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let index = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                Position::AfterIhdr,
+            )
+            .unwrap();
+
+        assert_eq!(index, 1);
+        assert_eq!(&png.chunks()[0].chunk_type().to_string(), "IHDR");
+
+        let png = Png::try_from(&png.as_bytes()[..]).unwrap();
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+    }
+
+    #[test]
+    fn test_insert_chunk_before_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let index = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                Position::BeforeIdat,
+            )
+            .unwrap();
+
+        assert_eq!(&png.chunks()[index + 1].chunk_type().to_string(), "IDAT");
+        assert!(Png::try_from(&png.as_bytes()[..]).is_ok());
+    }
+
+    #[test]
+    fn test_insert_chunk_after_index() {
+        let mut png = testing_png();
+        let index = png
+            .insert_chunk(
+                chunk_from_strings("TeSt", "Message").unwrap(),
+                Position::AfterChunk(0),
+            )
+            .unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+
+        let result = png.insert_chunk(
+            chunk_from_strings("TeSt", "Message").unwrap(),
+            Position::AfterChunk(10),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_insert_chunk_missing_anchor() {
+        let mut png = testing_png();
+        let chunk = chunk_from_strings("TeSt", "Message").unwrap();
+        assert!(png
+            .insert_chunk(chunk.clone(), Position::AfterIhdr)
+            .is_err());
+        assert!(png.insert_chunk(chunk, Position::BeforeIdat).is_err());
+    }
+
+    #[test]
+    fn test_position_from_str() {
+        assert_eq!(
+            Position::from_str("before-iend").unwrap(),
+            Position::BeforeIend
+        );
+        assert_eq!(
+            Position::from_str("after-ihdr").unwrap(),
+            Position::AfterIhdr
+        );
+        assert_eq!(
+            Position::from_str("before-idat").unwrap(),
+            Position::BeforeIdat
+        );
+        assert_eq!(
+            Position::from_str("after-3").unwrap(),
+            Position::AfterChunk(3)
+        );
+        assert!(Position::from_str("after-x").is_err());
+        assert!(Position::from_str("middle").is_err());
+    }
+
+    #[test]
+    fn test_remove_chunk_at() {
+        let mut png = testing_png();
+        let chunk = png.remove_chunk_at(1).unwrap();
+
+        assert_eq!(&chunk.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.remove_chunk_at(2).is_err());
+    }
+
+    #[test]
+    fn test_chunks_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("miDl", "I am a later chunk").unwrap());
+
+        let data: Vec<_> = png.chunks_by_type("miDl").map(|c| c.data()).collect();
+        assert_eq!(data, [&b"I am another chunk"[..], b"I am a later chunk"]);
+        assert_eq!(png.chunks_by_type("TeSt").count(), 0);
+    }
+
+    #[test]
+    fn test_remove_all() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("TeSt", "first").unwrap());
+        png.append_chunk(chunk_from_strings("TeSt", "second").unwrap());
+
+        let removed = png.remove_all("TeSt");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(removed[1].data(), b"second");
+        assert!(png.chunk_by_type("TeSt").is_none());
+        assert_eq!(png.chunks().len(), 3);
+
+        assert!(png.remove_all("TeSt").is_empty());
+    }
+
+    #[test]
+    fn test_remove_where() {
+        let mut png = testing_png();
+        let removed = png.remove_where(|c| c.data().starts_with(b"I am the"));
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&png.chunks()[0].chunk_type().to_string(), "miDl");
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut png = testing_png();
+        let removed = png.retain(|c| c.chunk_type().is_critical());
+
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "LASt");
+    }
+
+    #[test]
+    fn test_replace_chunk_at() {
+        let mut png = testing_png();
+        let old = png
+            .replace_chunk_at(0, chunk_from_strings("TeSt", "Message").unwrap())
+            .unwrap();
+
+        assert_eq!(&old.chunk_type().to_string(), "FrSt");
+        assert_eq!(&png.chunks()[0].chunk_type().to_string(), "TeSt");
+        assert!(png
+            .replace_chunk_at(3, chunk_from_strings("TeSt", "Message").unwrap())
+            .is_err());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -253,10 +721,110 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_chunk_reader() {
+        let mut reader = ChunkReader::new(&PNG_FILE[..]).unwrap();
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(&first.chunk_type().to_string(), "IHDR");
+
+        let rest: Vec<Chunk> = reader.collect::<Result<_>>().unwrap();
+        assert_eq!(
+            rest.len() + 1,
+            Png::try_from(&PNG_FILE[..]).unwrap().chunks().len()
+        );
+    }
+
+    #[test]
+    fn test_chunk_reader_truncated() {
+        let mut reader = ChunkReader::new(&PNG_FILE[..PNG_FILE.len() - 2]).unwrap();
+        assert!(reader.by_ref().any(|c| c.is_err()));
+        assert!(reader.next().is_none());
+    }
+
+    #[test]
+    fn test_chunk_reader_invalid_header() {
+        assert!(ChunkReader::new(&PNG_FILE[1..]).is_err());
+    }
+
+    #[test]
+    fn test_png_ref() {
+        let png_ref = PngRef::new(&PNG_FILE[..]).unwrap();
+        let chunks: Vec<ChunkRef> = png_ref.chunks().collect::<Result<_>>().unwrap();
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+
+        assert_eq!(chunks.len(), png.chunks().len());
+        assert_eq!(chunks[0].offset(), 8);
+        assert_eq!(chunks[1].offset(), 8 + 12 + chunks[0].data().len());
+        for (chunk_ref, chunk) in chunks.iter().zip(png.chunks()) {
+            assert_eq!(chunk_ref.data(), chunk.data());
+            assert!(chunk_ref.is_crc_valid());
+        }
+
+        assert_eq!(png_ref.to_png().unwrap().as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_chunk_infos() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let infos = png.chunk_infos();
+        let ref_infos = PngRef::new(&PNG_FILE[..]).unwrap().chunk_infos().unwrap();
+
+        assert_eq!(infos, ref_infos);
+        assert_eq!(infos[0].offset, 8);
+        assert_eq!(&infos[0].chunk_type, "IHDR");
+        assert!(infos.iter().all(|i| i.is_crc_valid()));
+
+        let last = infos.last().unwrap();
+        assert_eq!(
+            last.offset + last.length as usize + ChunkRef::OVERHEAD,
+            PNG_FILE.len()
+        );
+    }
+
+    #[test]
+    fn test_png_ref_invalid() {
+        assert!(PngRef::new(&PNG_FILE[1..]).is_err());
+
+        let png_ref = PngRef::new(&PNG_FILE[..PNG_FILE.len() - 2]).unwrap();
+        assert!(png_ref.chunks().any(|c| c.is_err()));
+        assert!(png_ref.to_png().is_err());
+    }
+
+    #[test]
+    fn test_write_to() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let bytes = png.write_to(Vec::new()).unwrap();
+        assert_eq!(bytes, PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_ihdr() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let ihdr = png.ihdr().unwrap();
+
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 50);
+        assert_eq!(ihdr.bit_depth, 8);
+
+        assert!(testing_png().ihdr().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let violations = png.validate();
+
+        // the test image carries a hidden `RuSt` chunk, which is critical
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].index, Some(5));
+
+        assert!(!testing_png().validate().is_empty());
+    }
+
     #[test]
     fn test_png_trait_impls() {
         let chunk_bytes: Vec<u8> = testing_chunks()
@@ -276,7 +844,7 @@ mod tests {
     }
 
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
-    const PNG_FILE: [u8; 4803] = [
+    pub(crate) const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
         6, 0, 0, 0, 30, 63, 136, 177, 0, 0, 0, 1, 115, 82, 71, 66, 0, 174, 206, 28, 233, 0, 0, 0,
         4, 103, 65, 77, 65, 0, 0, 177, 143, 11, 252, 97, 5, 0, 0, 0, 9, 112, 72, 89, 115, 0, 0, 14,
@@ -0,0 +1,272 @@
+use std::fmt::Display;
+
+use anyhow::{bail, Result};
+
+use crate::{
+    chunk::{Chunk, ChunkRef},
+    png::Png,
+};
+
+/// A problem found while parsing a damaged PNG
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Issue {
+    /// The stored crc doesn't match the chunk, the chunk is kept
+    BadCrc {
+        index: usize,
+        offset: usize,
+        stored: u32,
+        computed: u32,
+    },
+    /// The chunk type isn't made of letters or has the reserved bit set, the chunk is dropped
+    InvalidType {
+        index: usize,
+        offset: usize,
+        chunk_type: [u8; 4],
+    },
+    /// The chunk claims more data than the file has left, the rest of the file is dropped
+    Truncated {
+        index: usize,
+        offset: usize,
+        length: u32,
+        available: usize,
+    },
+    /// Bytes after `IEND` that don't form a chunk, they are dropped
+    TrailingBytes { offset: usize, length: usize },
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::BadCrc {
+                index,
+                offset,
+                stored,
+                computed,
+            } => write!(
+                f,
+                "chunk {index} at offset {offset}: stored crc {stored:#010x} doesn't match computed {computed:#010x}"
+            ),
+            Issue::InvalidType {
+                index,
+                offset,
+                chunk_type,
+            } => write!(
+                f,
+                "chunk {index} at offset {offset}: invalid chunk type {:?}",
+                String::from_utf8_lossy(chunk_type)
+            ),
+            Issue::Truncated {
+                index,
+                offset,
+                length,
+                available,
+            } => write!(
+                f,
+                "chunk {index} at offset {offset}: claims {length} bytes of data, only {available} bytes left for data and crc"
+            ),
+            Issue::TrailingBytes { offset, length } => {
+                write!(f, "{length} trailing bytes after IEND at offset {offset}")
+            }
+        }
+    }
+}
+
+/// What could be read from a damaged PNG
+#[derive(Clone, Debug)]
+pub struct Recovery {
+    pub png: Png,
+    pub issues: Vec<Issue>,
+}
+
+/// Parses `bytes` like [`Png::try_from`], but records problems instead of bailing
+///
+/// Only a broken PNG header is fatal.
+pub fn parse_lenient(bytes: &[u8]) -> Result<Recovery> {
+    if !bytes.starts_with(&Png::STANDARD_HEADER) {
+        bail!("invalid header")
+    }
+
+    let mut offset = Png::STANDARD_HEADER.len();
+    let mut rest = &bytes[offset..];
+    let mut chunks = vec![];
+    let mut issues = vec![];
+    let mut after_iend = false;
+    let mut index = 0;
+
+    while !rest.is_empty() {
+        let parsed = ChunkRef::parse_at(rest, offset);
+
+        let Ok((chunk_ref, next)) = parsed else {
+            let issue = match rest.get(..4) {
+                Some(length) if !after_iend => Issue::Truncated {
+                    index,
+                    offset,
+                    length: u32::from_be_bytes(length.try_into()?),
+                    // left after the length and type fields
+                    available: rest.len().saturating_sub(8),
+                },
+                _ => Issue::TrailingBytes {
+                    offset,
+                    length: rest.len(),
+                },
+            };
+            issues.push(issue);
+            break;
+        };
+
+        let chunk_type = chunk_ref.chunk_type_bytes();
+
+        match chunk_ref.chunk_type() {
+            Ok(t) if t.is_valid() => {
+                if !chunk_ref.is_crc_valid() {
+                    issues.push(Issue::BadCrc {
+                        index,
+                        offset,
+                        stored: chunk_ref.stored_crc(),
+                        computed: chunk_ref.crc(),
+                    });
+                }
+
+                chunks.push(Chunk::try_new(t, chunk_ref.data().to_vec())?);
+            }
+            _ if after_iend => {
+                issues.push(Issue::TrailingBytes {
+                    offset,
+                    length: rest.len(),
+                });
+                break;
+            }
+            _ => issues.push(Issue::InvalidType {
+                index,
+                offset,
+                chunk_type,
+            }),
+        }
+
+        after_iend |= chunk_type == *b"IEND";
+        offset += chunk_ref.as_bytes().len();
+        rest = next;
+        index += 1;
+    }
+
+    Ok(Recovery {
+        png: Png::from_chunks(chunks),
+        issues,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.as_bytes().to_vec(),
+        )
+    }
+
+    fn testing_bytes() -> Vec<u8> {
+        Png::from_chunks(vec![
+            chunk("IHDR", "header"),
+            chunk("ruSt", "hidden"),
+            chunk("IEND", ""),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_intact_png() {
+        let recovery = parse_lenient(&testing_bytes()).unwrap();
+
+        assert!(recovery.issues.is_empty());
+        assert_eq!(recovery.png.as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_bad_crc() {
+        let mut bytes = testing_bytes();
+        // last data byte of `ruSt`
+        bytes[8 + 18 + 13] ^= 1;
+
+        let recovery = parse_lenient(&bytes).unwrap();
+
+        assert!(matches!(
+            recovery.issues[..],
+            [Issue::BadCrc {
+                index: 1,
+                offset: 26,
+                ..
+            }]
+        ));
+        assert_eq!(recovery.png.chunks().len(), 3);
+        assert!(Png::try_from(&recovery.png.as_bytes()[..]).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_type() {
+        let mut bytes = testing_bytes();
+        bytes[8 + 18 + 4] = b'1';
+
+        let recovery = parse_lenient(&bytes).unwrap();
+
+        assert!(matches!(
+            recovery.issues[..],
+            [Issue::InvalidType { index: 1, .. }]
+        ));
+        assert_eq!(recovery.png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = testing_bytes();
+        let recovery = parse_lenient(&bytes[..bytes.len() - 14]).unwrap();
+
+        assert!(matches!(
+            recovery.issues[..],
+            [Issue::Truncated {
+                index: 1,
+                length: 6,
+                available: 8,
+                ..
+            }]
+        ));
+        assert_eq!(recovery.png.chunks().len(), 1);
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let mut bytes = testing_bytes();
+        let length = bytes.len();
+        bytes.extend_from_slice(b"garbage after the end");
+
+        let recovery = parse_lenient(&bytes).unwrap();
+
+        assert_eq!(
+            recovery.issues,
+            vec![Issue::TrailingBytes {
+                offset: length,
+                length: 21
+            }]
+        );
+        assert_eq!(recovery.png.as_bytes(), testing_bytes());
+    }
+
+    #[test]
+    fn test_chunks_after_iend_are_kept() {
+        let mut bytes = testing_bytes();
+        bytes.extend(chunk("ruSt", "appended").as_bytes());
+
+        let recovery = parse_lenient(&bytes).unwrap();
+
+        assert!(recovery.issues.is_empty());
+        assert_eq!(recovery.png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert!(parse_lenient(&testing_bytes()[1..]).is_err());
+    }
+}
@@ -0,0 +1,330 @@
+use serde::Serialize;
+
+use anyhow::Result;
+
+use crate::{
+    apng::AnimationInfo,
+    chunk::{Chunk, ChunkRef},
+    chunk_type::ChunkType,
+    envelope::FileInfo,
+    ihdr::Ihdr,
+};
+
+/// Bumped whenever a field is renamed or removed
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything pngme knows about a single chunk
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChunkInfo {
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+    pub length: u32,
+    /// The crc computed over the chunk type and data
+    pub crc: u32,
+    /// The crc as stored in the file
+    pub stored_crc: u32,
+    /// Byte offset of the chunk inside the file
+    pub offset: usize,
+    /// Hex encoded start of the chunk data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+impl ChunkInfo {
+    pub fn new(index: usize, offset: usize, chunk: &Chunk) -> Self {
+        Self::from_parts(
+            index,
+            offset,
+            chunk.chunk_type(),
+            chunk.length(),
+            chunk.crc(),
+            chunk.crc(),
+        )
+    }
+
+    /// Builds the info from a chunk as stored in its file, the crc may not match
+    pub fn from_ref(index: usize, chunk: &ChunkRef) -> Result<Self> {
+        Ok(Self::from_parts(
+            index,
+            chunk.offset(),
+            &chunk.chunk_type()?,
+            chunk.length(),
+            chunk.crc(),
+            chunk.stored_crc(),
+        ))
+    }
+
+    fn from_parts(
+        index: usize,
+        offset: usize,
+        chunk_type: &ChunkType,
+        length: u32,
+        crc: u32,
+        stored_crc: u32,
+    ) -> Self {
+        Self {
+            index,
+            chunk_type: chunk_type.to_string(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            length,
+            crc,
+            stored_crc,
+            offset,
+            preview: None,
+        }
+    }
+
+    /// Adds the first `length` bytes of `data` as hex
+    pub fn with_preview(mut self, data: &[u8], length: usize) -> Self {
+        let data = &data[..data.len().min(length)];
+        self.preview = Some(data.iter().map(|b| format!("{b:02x}")).collect());
+        self
+    }
+
+    pub fn is_crc_valid(&self) -> bool {
+        self.crc == self.stored_crc
+    }
+
+    /// The property bits in chunk type notation, uppercase meaning the bit is unset
+    pub fn flags(&self) -> String {
+        [
+            (self.critical, 'C', 'a'),
+            (self.public, 'P', 'p'),
+            (self.reserved_bit_valid, 'R', 'r'),
+            (self.safe_to_copy, 's', 'U'),
+        ]
+        .iter()
+        .map(|&(set, yes, no)| if set { yes } else { no })
+        .collect()
+    }
+}
+
+/// Output of `pngme print`
+#[derive(Clone, Debug, Serialize)]
+pub struct PrintReport {
+    pub schema_version: u32,
+    pub ihdr: Option<Ihdr>,
+    pub chunks: Vec<ChunkInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animation: Option<AnimationInfo>,
+    /// Why the animation chunks couldn't be read, telling a broken animation from none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub animation_error: Option<String>,
+}
+
+/// A message found by `pngme decode`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MessageInfo {
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    /// The message text, `null` rather than left out for embedded files
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileInfo>,
+}
+
+impl MessageInfo {
+    /// The message text, or a description of the embedded file
+    pub fn summary(&self) -> String {
+        match (&self.message, &self.file) {
+            (Some(message), _) => message.clone(),
+            (None, Some(file)) => file.to_string(),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Output of `pngme decode`
+#[derive(Clone, Debug, Serialize)]
+pub struct DecodeReport {
+    pub schema_version: u32,
+    pub messages: Vec<MessageInfo>,
+}
+
+/// Renders chunks as an aligned table, one row per chunk
+pub fn chunks_table(chunks: &[ChunkInfo]) -> String {
+    let mut table = format!(
+        "{:>5}  {:<4}  {:>10}  {:>10}  {:<10}  {}",
+        "INDEX", "TYPE", "OFFSET", "LENGTH", "CRC", "FLAGS"
+    );
+
+    for chunk in chunks {
+        table.push_str(&format!(
+            "\n{:>5}  {:<4}  {:>10}  {:>10}  {:#010x}  {}",
+            chunk.index,
+            chunk.chunk_type,
+            chunk.offset,
+            chunk.length,
+            chunk.crc,
+            chunk.flags()
+        ));
+
+        if let Some(preview) = &chunk.preview {
+            table.push_str(&format!("  {preview}"));
+        }
+    }
+
+    table
+}
+
+/// Renders every detail of the chunks, one block per chunk
+pub fn long_listing(chunks: &[ChunkInfo]) -> String {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+    chunks
+        .iter()
+        .map(|chunk| {
+            let crc = match chunk.is_crc_valid() {
+                true => format!("{:#010x} (ok)", chunk.crc),
+                false => format!(
+                    "{:#010x} stored, {:#010x} computed (mismatch)",
+                    chunk.stored_crc, chunk.crc
+                ),
+            };
+
+            let mut block = format!(
+                "#{} {} at offset {}\n  length: {}\n  crc: {crc}\n  critical: {}, public: {}, reserved bit valid: {}, safe to copy: {}",
+                chunk.index,
+                chunk.chunk_type,
+                chunk.offset,
+                chunk.length,
+                yes_no(chunk.critical),
+                yes_no(chunk.public),
+                yes_no(chunk.reserved_bit_valid),
+                yes_no(chunk.safe_to_copy),
+            );
+
+            if let Some(preview) = &chunk.preview {
+                block.push_str(&format!("\n  data: {preview}"));
+            }
+
+            block
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders messages as an aligned table, one row per message
+pub fn messages_table(messages: &[MessageInfo]) -> String {
+    let mut table = format!("{:>5}  {:<4}  MESSAGE", "INDEX", "TYPE");
+
+    for message in messages {
+        table.push_str(&format!(
+            "\n{:>5}  {:<4}  {}",
+            message.index,
+            message.chunk_type,
+            message.summary().escape_debug()
+        ));
+    }
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_chunk() -> Chunk {
+        Chunk::new(
+            ChunkType::from_str("RuSt").unwrap(),
+            "This is where your secret message will be!"
+                .as_bytes()
+                .to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_chunk_info() {
+        let info = ChunkInfo::new(2, 33, &testing_chunk());
+
+        assert_eq!(info.chunk_type, "RuSt");
+        assert!(info.critical);
+        assert!(!info.public);
+        assert!(info.reserved_bit_valid);
+        assert!(info.safe_to_copy);
+        assert_eq!(info.length, 42);
+        assert_eq!(info.crc, 2882656334);
+        assert_eq!(info.flags(), "CpRs");
+    }
+
+    #[test]
+    fn test_chunk_info_json() {
+        let info = ChunkInfo::new(2, 33, &testing_chunk());
+        let json = serde_json::to_value(&info).unwrap();
+
+        assert_eq!(json["type"], "RuSt");
+        assert_eq!(json["offset"], 33);
+        assert!(json.get("preview").is_none());
+
+        let info = info.with_preview(testing_chunk().data(), 4);
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["preview"], "54686973");
+    }
+
+    #[test]
+    fn test_message_info_json_keeps_message() {
+        let info = MessageInfo {
+            index: 1,
+            chunk_type: "ruSt".to_string(),
+            message: None,
+            file: Some(FileInfo::new("notes.txt", b"notes")),
+        };
+        let json = serde_json::to_value(&info).unwrap();
+
+        assert_eq!(json.get("message"), Some(&serde_json::Value::Null));
+        assert_eq!(json["file"]["name"], "notes.txt");
+    }
+
+    #[test]
+    fn test_print_report_json_animation_error() {
+        let report = PrintReport {
+            schema_version: SCHEMA_VERSION,
+            ihdr: None,
+            chunks: vec![],
+            animation: None,
+            animation_error: Some("acTL is too short".to_string()),
+        };
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert!(json.get("animation").is_none());
+        assert_eq!(json["animation_error"], "acTL is too short");
+    }
+
+    #[test]
+    fn test_chunk_info_from_ref() {
+        let mut bytes = testing_chunk().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+
+        let (chunk_ref, _) = ChunkRef::parse_at(&bytes, 8).unwrap();
+        let info = ChunkInfo::from_ref(1, &chunk_ref).unwrap();
+
+        assert_eq!(info.offset, 8);
+        assert_eq!(info.crc, 2882656334);
+        assert_eq!(info.stored_crc, 2882656334 ^ 1);
+        assert!(!info.is_crc_valid());
+        assert!(long_listing(&[info]).contains("mismatch"));
+    }
+
+    #[test]
+    fn test_chunks_table() {
+        let info = ChunkInfo::new(0, 8, &testing_chunk());
+        let table = chunks_table(&[info]);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].contains("RuSt"));
+        assert!(lines[1].contains("0xabd1d84e"));
+    }
+}
@@ -0,0 +1,1357 @@
+use std::{
+    fmt::Display,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    ihdr::{ColorType, Ihdr},
+    png::{Png, Position},
+    text,
+};
+
+/// The chunk types [`StandardChunk`] understands
+pub const CHUNK_TYPES: [&str; 11] = [
+    "PLTE", "tRNS", "gAMA", "cHRM", "sRGB", "pHYs", "sBIT", "bKGD", "hIST", "sPLT", "tIME",
+];
+
+/// One color of a `PLTE` chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaletteEntry {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Display for PaletteEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+/// A color in the samples of the image, so at its bit depth
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Gray(u16),
+    Rgb {
+        red: u16,
+        green: u16,
+        blue: u16,
+    },
+    /// An entry of the palette
+    Index(u8),
+}
+
+impl Color {
+    fn parse(data: &[u8], color_type: ColorType) -> Result<Self> {
+        Ok(match (color_type, data) {
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, [_, _]) => {
+                Color::Gray(u16_at(data, 0))
+            }
+            (ColorType::Rgb | ColorType::Rgba, [_, _, _, _, _, _]) => Color::Rgb {
+                red: u16_at(data, 0),
+                green: u16_at(data, 2),
+                blue: u16_at(data, 4),
+            },
+            (ColorType::Indexed, [index]) => Color::Index(*index),
+            _ => bail!(
+                "expected {} bytes for a {color_type} image, found {}",
+                Self::length(color_type),
+                data.len()
+            ),
+        })
+    }
+
+    /// Parses `#rrggbb` or comma separated samples, as many as `color_type` has colors
+    ///
+    /// Indexed images take a single palette index.
+    pub fn parse_samples(s: &str, color_type: ColorType) -> Result<Self> {
+        let samples: Vec<u16> = match s.strip_prefix('#') {
+            Some(hex) if hex.len() == 6 && hex.is_ascii() => (0..6)
+                .step_by(2)
+                .map(|i| u16::from_str_radix(&hex[i..i + 2], 16))
+                .collect::<Result<_, _>>()?,
+            Some(_) => bail!("expected a color like `#rrggbb`, not `{s}`"),
+            None => s
+                .split(',')
+                .map(|sample| sample.trim().parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| anyhow!("expected comma separated samples, not `{s}`"))?,
+        };
+
+        Ok(match (color_type, samples.as_slice()) {
+            (ColorType::Grayscale | ColorType::GrayscaleAlpha, [gray]) => Color::Gray(*gray),
+            (ColorType::Rgb | ColorType::Rgba, [red, green, blue]) => Color::Rgb {
+                red: *red,
+                green: *green,
+                blue: *blue,
+            },
+            (ColorType::Indexed, [index]) => Color::Index(u8::try_from(*index)?),
+            (color_type, samples) => bail!(
+                "{} sample(s) given, a {color_type} image takes {}",
+                samples.len(),
+                match color_type {
+                    ColorType::Rgb | ColorType::Rgba => "a `#rrggbb` color or 3 samples",
+                    ColorType::Indexed => "a palette index",
+                    _ => "a single gray sample",
+                }
+            ),
+        })
+    }
+
+    fn length(color_type: ColorType) -> usize {
+        match color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb | ColorType::Rgba => 6,
+            ColorType::Indexed => 1,
+        }
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        match self {
+            Color::Gray(gray) => gray.to_be_bytes().to_vec(),
+            Color::Rgb { red, green, blue } => [red, green, blue]
+                .iter()
+                .flat_map(|s| s.to_be_bytes())
+                .collect(),
+            Color::Index(index) => vec![*index],
+        }
+    }
+
+    /// Checks the color fits the color type and bit depth of the image
+    fn validate(&self, ihdr: &Ihdr, palette: Option<&[PaletteEntry]>) -> Result<()> {
+        let max = match ihdr.color_type {
+            ColorType::Indexed => 255,
+            _ => ((1u32 << ihdr.bit_depth) - 1) as u16,
+        };
+
+        match (self, ihdr.color_type) {
+            (Color::Gray(gray), ColorType::Grayscale | ColorType::GrayscaleAlpha) => {
+                check_sample(*gray, max)
+            }
+            (Color::Rgb { red, green, blue }, ColorType::Rgb | ColorType::Rgba) => {
+                for sample in [red, green, blue] {
+                    check_sample(*sample, max)?;
+                }
+                Ok(())
+            }
+            (Color::Index(index), ColorType::Indexed) => match palette {
+                Some(palette) if usize::from(*index) < palette.len() => Ok(()),
+                Some(palette) => bail!(
+                    "palette index {index} is out of range, the palette has {} entries",
+                    palette.len()
+                ),
+                None => bail!("palette index given but there is no palette"),
+            },
+            (_, color_type) => bail!("{self} doesn't match the color type {color_type}"),
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Color::Gray(gray) => write!(f, "gray {gray}"),
+            Color::Rgb { red, green, blue } => write!(f, "rgb({red}, {green}, {blue})"),
+            Color::Index(index) => write!(f, "palette index {index}"),
+        }
+    }
+}
+
+/// Transparency without a full alpha channel, from a `tRNS` chunk
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Transparency {
+    /// Pixels of exactly this gray or rgb color are transparent
+    Color(Color),
+    /// Alpha values of the first palette entries, the others are opaque
+    Alphas(Vec<u8>),
+}
+
+/// A CIE 1931 xy chromaticity, times 100000
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chromaticity {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Display for Chromaticity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "({:.5}, {:.5})",
+            f64::from(self.x) / 100_000.0,
+            f64::from(self.y) / 100_000.0
+        )
+    }
+}
+
+/// White point and primaries of a `cHRM` chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Chromaticities {
+    pub white: Chromaticity,
+    pub red: Chromaticity,
+    pub green: Chromaticity,
+    pub blue: Chromaticity,
+}
+
+impl Chromaticities {
+    /// The white point and primaries of sRGB
+    pub const SRGB: Chromaticities = Chromaticities {
+        white: Chromaticity { x: 31270, y: 32900 },
+        red: Chromaticity { x: 64000, y: 33000 },
+        green: Chromaticity { x: 30000, y: 60000 },
+        blue: Chromaticity { x: 15000, y: 6000 },
+    };
+}
+
+impl FromStr for Chromaticities {
+    type Err = Error;
+
+    /// Parses `srgb` or the eight comma separated coordinates of white, red, green and blue
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "srgb" {
+            return Ok(Self::SRGB);
+        }
+
+        let values = s
+            .split(',')
+            .map(|v| fixed_point(v.trim()))
+            .collect::<Result<Vec<_>>>()?;
+
+        let [wx, wy, rx, ry, gx, gy, bx, by] = values[..] else {
+            bail!(
+                "expected `srgb` or 8 comma separated values, found {}",
+                values.len()
+            );
+        };
+
+        Ok(Chromaticities {
+            white: Chromaticity { x: wx, y: wy },
+            red: Chromaticity { x: rx, y: ry },
+            green: Chromaticity { x: gx, y: gy },
+            blue: Chromaticity { x: bx, y: by },
+        })
+    }
+}
+
+/// How colors outside the target gamut are mapped, from an `sRGB` chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual = 0,
+    RelativeColorimetric = 1,
+    Saturation = 2,
+    AbsoluteColorimetric = 3,
+}
+
+impl TryFrom<u8> for RenderingIntent {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => RenderingIntent::Perceptual,
+            1 => RenderingIntent::RelativeColorimetric,
+            2 => RenderingIntent::Saturation,
+            3 => RenderingIntent::AbsoluteColorimetric,
+            _ => bail!("invalid rendering intent {value}"),
+        })
+    }
+}
+
+impl FromStr for RenderingIntent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "perceptual" => RenderingIntent::Perceptual,
+            "relative" | "relative-colorimetric" => RenderingIntent::RelativeColorimetric,
+            "saturation" => RenderingIntent::Saturation,
+            "absolute" | "absolute-colorimetric" => RenderingIntent::AbsoluteColorimetric,
+            _ => bail!(
+                "rendering intent has to be one of `perceptual`, `relative`, `saturation` or `absolute`"
+            ),
+        })
+    }
+}
+
+impl Display for RenderingIntent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhysicalUnit {
+    /// Only the aspect ratio is known
+    Unknown = 0,
+    Meter = 1,
+}
+
+/// Intended pixel size or aspect ratio, from a `pHYs` chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    /// Pixels per unit along the x axis
+    pub x: u32,
+    /// Pixels per unit along the y axis
+    pub y: u32,
+    pub unit: PhysicalUnit,
+}
+
+impl PhysicalDimensions {
+    const METERS_PER_INCH: f64 = 0.0254;
+
+    /// Pixels per meter matching the given pixels per inch
+    pub fn from_dpi(x: f64, y: f64) -> Result<Self> {
+        let per_meter = |dpi: f64| {
+            let value = (dpi / Self::METERS_PER_INCH).round();
+            match (1.0..=f64::from(u32::MAX)).contains(&value) {
+                true => Ok(value as u32),
+                false => bail!("{dpi} dpi is out of range"),
+            }
+        };
+
+        Ok(Self {
+            x: per_meter(x)?,
+            y: per_meter(y)?,
+            unit: PhysicalUnit::Meter,
+        })
+    }
+
+    /// Pixels per inch along both axes, if the unit is known
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            PhysicalUnit::Meter => Some((
+                f64::from(self.x) * Self::METERS_PER_INCH,
+                f64::from(self.y) * Self::METERS_PER_INCH,
+            )),
+            PhysicalUnit::Unknown => None,
+        }
+    }
+}
+
+/// One color of an `sPLT` chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SuggestedEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    /// Relative usage of this color
+    pub frequency: u16,
+}
+
+/// A palette suggested for displays with fewer colors, from an `sPLT` chunk
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SuggestedPalette {
+    pub name: String,
+    /// Bits per sample of the entries, 8 or 16
+    pub depth: u8,
+    pub entries: Vec<SuggestedEntry>,
+}
+
+/// Time of the last modification in UTC, from a `tIME` chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    /// The current time
+    pub fn now() -> Result<Self> {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        Self::from_unix(seconds)
+    }
+
+    /// The time `seconds` after the Unix epoch
+    pub fn from_unix(seconds: u64) -> Result<Self> {
+        let (days, time) = (seconds / 86_400, seconds % 86_400);
+
+        // Howard Hinnant's civil_from_days, shifted so years start in March
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+        Ok(Time {
+            year: u16::try_from(year).map_err(|_| anyhow!("year {year} doesn't fit tIME"))?,
+            month: month as u8,
+            day: day as u8,
+            hour: (time / 3600) as u8,
+            minute: (time / 60 % 60) as u8,
+            second: (time % 60) as u8,
+        })
+    }
+
+    /// Checks every field is within its range
+    fn validate(&self) -> Result<()> {
+        let fields = [
+            ("month", self.month, 1, 12),
+            ("day", self.day, 1, 31),
+            ("hour", self.hour, 0, 23),
+            ("minute", self.minute, 0, 59),
+            // leap seconds
+            ("second", self.second, 0, 60),
+        ];
+
+        for (name, value, min, max) in fields {
+            if !(min..=max).contains(&value) {
+                bail!("{name} {value} is out of range, expected {min} to {max}");
+            }
+        }
+
+        let leap = self.year.is_multiple_of(4)
+            && (!self.year.is_multiple_of(100) || self.year.is_multiple_of(400));
+        let days = match self.month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        if self.day > days {
+            bail!(
+                "day {} is out of range, month {} has {days} days",
+                self.day,
+                self.month
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Time {
+    type Err = Error;
+
+    /// Parses `YYYY-MM-DD HH:MM:SS` in UTC, a `T` may separate date and time
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || anyhow!("expected a time like `2024-01-31 12:00:00`, not `{s}`");
+
+        let (date, time) = s
+            .trim_end_matches('Z')
+            .split_once([' ', 'T'])
+            .ok_or_else(invalid)?;
+
+        let numbers = |s: &str, separator| -> Result<Vec<u16>> {
+            s.split(separator)
+                .map(|n| n.parse().map_err(|_| invalid()))
+                .collect()
+        };
+
+        let (date, time) = (numbers(date, '-')?, numbers(time, ':')?);
+        let (&[year, month, day], &[hour, minute, second]) = (date.as_slice(), time.as_slice())
+        else {
+            return Err(invalid());
+        };
+
+        let field = |n: u16| u8::try_from(n).map_err(|_| invalid());
+        let time = Time {
+            year,
+            month: field(month)?,
+            day: field(day)?,
+            hour: field(hour)?,
+            minute: field(minute)?,
+            second: field(second)?,
+        };
+
+        time.validate()?;
+
+        Ok(time)
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// A chunk defined by the PNG spec, other than the image header, data and text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StandardChunk {
+    /// `PLTE`
+    Palette(Vec<PaletteEntry>),
+    /// `tRNS`
+    Transparency(Transparency),
+    /// `gAMA`, the image gamma times 100000
+    Gamma(u32),
+    /// `cHRM`
+    Chromaticities(Chromaticities),
+    /// `sRGB`
+    Srgb(RenderingIntent),
+    /// `pHYs`
+    PhysicalDimensions(PhysicalDimensions),
+    /// `sBIT`, the significant bits of every channel
+    SignificantBits(Vec<u8>),
+    /// `bKGD`
+    Background(Color),
+    /// `hIST`, how often every palette entry is used
+    Histogram(Vec<u16>),
+    /// `sPLT`
+    SuggestedPalette(SuggestedPalette),
+    /// `tIME`
+    Time(Time),
+}
+
+impl StandardChunk {
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            StandardChunk::Palette(_) => "PLTE",
+            StandardChunk::Transparency(_) => "tRNS",
+            StandardChunk::Gamma(_) => "gAMA",
+            StandardChunk::Chromaticities(_) => "cHRM",
+            StandardChunk::Srgb(_) => "sRGB",
+            StandardChunk::PhysicalDimensions(_) => "pHYs",
+            StandardChunk::SignificantBits(_) => "sBIT",
+            StandardChunk::Background(_) => "bKGD",
+            StandardChunk::Histogram(_) => "hIST",
+            StandardChunk::SuggestedPalette(_) => "sPLT",
+            StandardChunk::Time(_) => "tIME",
+        }
+    }
+
+    pub fn is_standard_chunk(chunk: &Chunk) -> bool {
+        CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str())
+    }
+
+    /// Parses `chunk`, the layout of some chunks depends on the color type in `ihdr`
+    pub fn parse(chunk: &Chunk, ihdr: &Ihdr) -> Result<Self> {
+        let chunk_type = chunk.chunk_type().to_string();
+        let data = chunk.data();
+
+        let expect_length = |length: usize| -> Result<()> {
+            match data.len() == length {
+                true => Ok(()),
+                false => bail!("expected {length} bytes, found {}", data.len()),
+            }
+        };
+
+        Ok(match chunk_type.as_str() {
+            "PLTE" => {
+                if !data.len().is_multiple_of(3) {
+                    bail!("length {} is not divisible by 3", data.len());
+                }
+
+                StandardChunk::Palette(
+                    data.chunks_exact(3)
+                        .map(|c| PaletteEntry {
+                            red: c[0],
+                            green: c[1],
+                            blue: c[2],
+                        })
+                        .collect(),
+                )
+            }
+            "tRNS" => StandardChunk::Transparency(match ihdr.color_type {
+                ColorType::Indexed => Transparency::Alphas(data.to_vec()),
+                ColorType::Grayscale | ColorType::Rgb => {
+                    Transparency::Color(Color::parse(data, ihdr.color_type)?)
+                }
+                color_type => bail!("not allowed for {color_type} images"),
+            }),
+            "gAMA" => {
+                expect_length(4)?;
+                StandardChunk::Gamma(u32_at(data, 0))
+            }
+            "cHRM" => {
+                expect_length(32)?;
+                let at = |i: usize| Chromaticity {
+                    x: u32_at(data, i * 8),
+                    y: u32_at(data, i * 8 + 4),
+                };
+
+                StandardChunk::Chromaticities(Chromaticities {
+                    white: at(0),
+                    red: at(1),
+                    green: at(2),
+                    blue: at(3),
+                })
+            }
+            "sRGB" => {
+                expect_length(1)?;
+                StandardChunk::Srgb(RenderingIntent::try_from(data[0])?)
+            }
+            "pHYs" => {
+                expect_length(9)?;
+                let unit = match data[8] {
+                    0 => PhysicalUnit::Unknown,
+                    1 => PhysicalUnit::Meter,
+                    unit => bail!("invalid unit {unit}"),
+                };
+
+                StandardChunk::PhysicalDimensions(PhysicalDimensions {
+                    x: u32_at(data, 0),
+                    y: u32_at(data, 4),
+                    unit,
+                })
+            }
+            "sBIT" => {
+                expect_length(significant_bits_length(ihdr.color_type))?;
+                StandardChunk::SignificantBits(data.to_vec())
+            }
+            "bKGD" => StandardChunk::Background(Color::parse(data, ihdr.color_type)?),
+            "hIST" => {
+                if !data.len().is_multiple_of(2) {
+                    bail!("length {} is not divisible by 2", data.len());
+                }
+
+                StandardChunk::Histogram(
+                    (0..data.len())
+                        .step_by(2)
+                        .map(|i| u16_at(data, i))
+                        .collect(),
+                )
+            }
+            "sPLT" => {
+                let (name, rest) = text::split_at_null(data)?;
+                let Some((&depth, entries)) = rest.split_first() else {
+                    bail!("missing sample depth");
+                };
+
+                let entry_length = match depth {
+                    8 => 6,
+                    16 => 10,
+                    _ => bail!("invalid sample depth {depth}"),
+                };
+
+                if !entries.len().is_multiple_of(entry_length) {
+                    bail!("entries are not a multiple of {entry_length} bytes");
+                }
+
+                let entries = entries
+                    .chunks_exact(entry_length)
+                    .map(|e| match depth {
+                        8 => SuggestedEntry {
+                            red: e[0].into(),
+                            green: e[1].into(),
+                            blue: e[2].into(),
+                            alpha: e[3].into(),
+                            frequency: u16_at(e, 4),
+                        },
+                        _ => SuggestedEntry {
+                            red: u16_at(e, 0),
+                            green: u16_at(e, 2),
+                            blue: u16_at(e, 4),
+                            alpha: u16_at(e, 6),
+                            frequency: u16_at(e, 8),
+                        },
+                    })
+                    .collect();
+
+                StandardChunk::SuggestedPalette(SuggestedPalette {
+                    name: text::from_latin1(name),
+                    depth,
+                    entries,
+                })
+            }
+            "tIME" => {
+                expect_length(7)?;
+                StandardChunk::Time(Time {
+                    year: u16_at(data, 0),
+                    month: data[2],
+                    day: data[3],
+                    hour: data[4],
+                    minute: data[5],
+                    second: data[6],
+                })
+            }
+            _ => bail!("{chunk_type} is not a standard chunk"),
+        })
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = vec![];
+
+        match self {
+            StandardChunk::Palette(entries) => {
+                for entry in entries {
+                    data.extend([entry.red, entry.green, entry.blue]);
+                }
+            }
+            StandardChunk::Transparency(Transparency::Color(color)) => data = color.as_bytes(),
+            StandardChunk::Transparency(Transparency::Alphas(alphas)) => data = alphas.clone(),
+            StandardChunk::Gamma(gamma) => data.extend(gamma.to_be_bytes()),
+            StandardChunk::Chromaticities(c) => {
+                for point in [c.white, c.red, c.green, c.blue] {
+                    data.extend(point.x.to_be_bytes());
+                    data.extend(point.y.to_be_bytes());
+                }
+            }
+            StandardChunk::Srgb(intent) => data.push(*intent as u8),
+            StandardChunk::PhysicalDimensions(p) => {
+                data.extend(p.x.to_be_bytes());
+                data.extend(p.y.to_be_bytes());
+                data.push(p.unit as u8);
+            }
+            StandardChunk::SignificantBits(bits) => data = bits.clone(),
+            StandardChunk::Background(color) => data = color.as_bytes(),
+            StandardChunk::Histogram(frequencies) => {
+                data = frequencies.iter().flat_map(|f| f.to_be_bytes()).collect();
+            }
+            StandardChunk::SuggestedPalette(palette) => {
+                data = text::keyword_to_latin1(&palette.name)?;
+                data.extend([0, palette.depth]);
+
+                for e in &palette.entries {
+                    for sample in [e.red, e.green, e.blue, e.alpha] {
+                        match palette.depth {
+                            8 => data.push(sample as u8),
+                            _ => data.extend(sample.to_be_bytes()),
+                        }
+                    }
+                    data.extend(e.frequency.to_be_bytes());
+                }
+            }
+            StandardChunk::Time(time) => {
+                data.extend(time.year.to_be_bytes());
+                data.extend([time.month, time.day, time.hour, time.minute, time.second]);
+            }
+        }
+
+        Chunk::try_new(ChunkType::from_str(self.chunk_type())?, data)
+    }
+
+    /// Checks the fields against the ranges the spec allows for the image
+    ///
+    /// Some chunks refer to the palette, which is `None` if the image has none.
+    pub fn validate(&self, ihdr: &Ihdr, palette: Option<&[PaletteEntry]>) -> Result<()> {
+        match self {
+            StandardChunk::Palette(entries) => {
+                if entries.is_empty() || entries.len() > 256 {
+                    bail!("palette has {} entries, expected 1 to 256", entries.len());
+                }
+
+                match ihdr.color_type {
+                    ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                        bail!("palette is not allowed for {} images", ihdr.color_type)
+                    }
+                    ColorType::Indexed if entries.len() > 1 << ihdr.bit_depth => bail!(
+                        "palette has {} entries, more than a bit depth of {} can address",
+                        entries.len(),
+                        ihdr.bit_depth
+                    ),
+                    _ => {}
+                }
+            }
+            StandardChunk::Transparency(Transparency::Color(color)) => {
+                if matches!(color, Color::Index(_)) {
+                    bail!("transparency of indexed images is given as alpha values");
+                }
+                color.validate(ihdr, palette)?;
+            }
+            StandardChunk::Transparency(Transparency::Alphas(alphas)) => {
+                if ihdr.color_type != ColorType::Indexed {
+                    bail!("alpha values are only allowed for indexed images");
+                }
+
+                let entries = palette.map_or(0, |p| p.len());
+                if alphas.len() > entries {
+                    bail!(
+                        "{} alpha values for a palette of {entries} entries",
+                        alphas.len()
+                    );
+                }
+            }
+            StandardChunk::Gamma(0) => bail!("gamma must not be zero"),
+            StandardChunk::Gamma(_)
+            | StandardChunk::Chromaticities(_)
+            | StandardChunk::Srgb(_)
+            | StandardChunk::PhysicalDimensions(_) => {}
+            StandardChunk::SignificantBits(bits) => {
+                let expected = significant_bits_length(ihdr.color_type);
+                if bits.len() != expected {
+                    bail!("expected {expected} values, found {}", bits.len());
+                }
+
+                let depth = match ihdr.color_type {
+                    ColorType::Indexed => 8,
+                    _ => ihdr.bit_depth,
+                };
+                if let Some(bits) = bits.iter().find(|b| **b == 0 || **b > depth) {
+                    bail!("{bits} significant bits, expected 1 to {depth}");
+                }
+            }
+            StandardChunk::Background(color) => color.validate(ihdr, palette)?,
+            StandardChunk::Histogram(frequencies) => match palette {
+                Some(palette) if palette.len() == frequencies.len() => {}
+                Some(palette) => bail!(
+                    "{} frequencies for a palette of {} entries",
+                    frequencies.len(),
+                    palette.len()
+                ),
+                None => bail!("histogram without a palette"),
+            },
+            StandardChunk::SuggestedPalette(palette) => {
+                text::keyword_to_latin1(&palette.name)?;
+
+                match palette.depth {
+                    8 => {
+                        let too_large = palette
+                            .entries
+                            .iter()
+                            .any(|e| [e.red, e.green, e.blue, e.alpha].iter().any(|s| *s > 255));
+                        if too_large {
+                            bail!("samples of a palette with a depth of 8 have to be below 256");
+                        }
+                    }
+                    16 => {}
+                    depth => bail!("invalid sample depth {depth}"),
+                }
+            }
+            StandardChunk::Time(time) => time.validate()?,
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for StandardChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StandardChunk::Palette(entries) => {
+                write!(f, "{} entries", entries.len())?;
+                for entry in entries.iter().take(8) {
+                    write!(f, " {entry}")?;
+                }
+                if entries.len() > 8 {
+                    write!(f, " ...")?;
+                }
+                Ok(())
+            }
+            StandardChunk::Transparency(Transparency::Color(color)) => {
+                write!(f, "{color} is transparent")
+            }
+            StandardChunk::Transparency(Transparency::Alphas(alphas)) => {
+                write!(f, "alpha of the first {} palette entries", alphas.len())
+            }
+            StandardChunk::Gamma(gamma) => write!(f, "{:.5}", f64::from(*gamma) / 100_000.0),
+            StandardChunk::Chromaticities(c) => write!(
+                f,
+                "white {}, red {}, green {}, blue {}",
+                c.white, c.red, c.green, c.blue
+            ),
+            StandardChunk::Srgb(intent) => write!(f, "{intent} rendering intent"),
+            StandardChunk::PhysicalDimensions(p) => match p.dpi() {
+                Some((x, y)) => write!(
+                    f,
+                    "{} x {} pixels per meter ({x:.0} x {y:.0} DPI)",
+                    p.x, p.y
+                ),
+                None => write!(f, "aspect ratio {}:{}", p.x, p.y),
+            },
+            StandardChunk::SignificantBits(bits) => {
+                let bits: Vec<_> = bits.iter().map(|b| b.to_string()).collect();
+                write!(f, "{} significant bits", bits.join(", "))
+            }
+            StandardChunk::Background(color) => write!(f, "{color}"),
+            StandardChunk::Histogram(frequencies) => {
+                write!(f, "frequencies of {} palette entries", frequencies.len())
+            }
+            StandardChunk::SuggestedPalette(palette) => write!(
+                f,
+                "\"{}\", {} entries of depth {}",
+                palette.name,
+                palette.entries.len(),
+                palette.depth
+            ),
+            StandardChunk::Time(time) => write!(f, "last modified {time}"),
+        }
+    }
+}
+
+/// Parses and validates every standard chunk of `png`, keeping the index of each
+pub fn list(png: &Png) -> Result<Vec<(usize, Result<StandardChunk>)>> {
+    let ihdr = png.ihdr()?;
+    let palette = palette(png, &ihdr);
+
+    Ok(png
+        .chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| StandardChunk::is_standard_chunk(c))
+        .map(|(i, c)| {
+            let chunk = StandardChunk::parse(c, &ihdr).and_then(|chunk| {
+                chunk.validate(&ihdr, palette.as_deref())?;
+                Ok(chunk)
+            });
+            (i, chunk)
+        })
+        .collect())
+}
+
+/// Validates `chunk` and puts it where the spec requires, replacing any chunk of the same type
+///
+/// Returns the index of the chunk.
+pub fn set(png: &mut Png, chunk: &StandardChunk) -> Result<usize> {
+    let ihdr = png.ihdr()?;
+    let palette = match chunk {
+        StandardChunk::Palette(_) => None,
+        _ => palette(png, &ihdr),
+    };
+    chunk.validate(&ihdr, palette.as_deref())?;
+
+    let chunk_type = chunk.chunk_type();
+    png.remove_all(chunk_type);
+
+    let first_of = |types: &[&str]| {
+        png.chunks()
+            .iter()
+            .position(|c| types.contains(&c.chunk_type().to_string().as_str()))
+    };
+
+    let before = match chunk_type {
+        "gAMA" | "cHRM" | "sRGB" | "sBIT" => first_of(&["PLTE", "IDAT"]),
+        "PLTE" => first_of(&["tRNS", "bKGD", "hIST", "IDAT"]),
+        "tRNS" | "bKGD" | "hIST" | "pHYs" | "sPLT" => first_of(&["IDAT"]),
+        _ => None,
+    };
+
+    let position = match before {
+        // IHDR is the first chunk, so anything is placed after it
+        Some(index) => Position::AfterChunk(index - 1),
+        None => Position::BeforeIend,
+    };
+
+    png.insert_chunk(chunk.to_chunk()?, position)
+}
+
+fn palette(png: &Png, ihdr: &Ihdr) -> Option<Vec<PaletteEntry>> {
+    match StandardChunk::parse(png.chunk_by_type("PLTE")?, ihdr) {
+        Ok(StandardChunk::Palette(entries)) => Some(entries),
+        _ => None,
+    }
+}
+
+/// Parses a decimal like `0.45455` into the value times 100000 chunks store
+pub fn fixed_point(s: &str) -> Result<u32> {
+    match s.parse::<f64>() {
+        Ok(value) if (0.0..=f64::from(u32::MAX) / 100_000.0).contains(&value) => {
+            Ok((value * 100_000.0).round() as u32)
+        }
+        _ => bail!("expected a positive decimal number, not `{s}`"),
+    }
+}
+
+fn significant_bits_length(color_type: ColorType) -> usize {
+    match color_type {
+        ColorType::Indexed => 3,
+        color_type => color_type.channels().into(),
+    }
+}
+
+fn check_sample(sample: u16, max: u16) -> Result<()> {
+    match sample <= max {
+        true => Ok(()),
+        false => bail!("sample {sample} is too large for the bit depth, expected at most {max}"),
+    }
+}
+
+fn u16_at(data: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([data[i], data[i + 1]])
+}
+
+fn u32_at(data: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::InterlaceMethod;
+
+    fn ihdr(color_type: ColorType, bit_depth: u8) -> Ihdr {
+        Ihdr {
+            width: 4,
+            height: 4,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    fn palette(entries: u8) -> Vec<PaletteEntry> {
+        (0..entries)
+            .map(|i| PaletteEntry {
+                red: i,
+                green: i,
+                blue: i,
+            })
+            .collect()
+    }
+
+    fn assert_round_trip(chunk: StandardChunk, ihdr: &Ihdr) {
+        let parsed = StandardChunk::parse(&chunk.to_chunk().unwrap(), ihdr).unwrap();
+        assert_eq!(parsed, chunk);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let rgb = ihdr(ColorType::Rgb, 8);
+        let chunks = [
+            StandardChunk::Palette(palette(3)),
+            StandardChunk::Transparency(Transparency::Color(Color::Rgb {
+                red: 1,
+                green: 2,
+                blue: 3,
+            })),
+            StandardChunk::Gamma(45455),
+            StandardChunk::Chromaticities(Chromaticities {
+                white: Chromaticity { x: 31270, y: 32900 },
+                red: Chromaticity { x: 64000, y: 33000 },
+                green: Chromaticity { x: 30000, y: 60000 },
+                blue: Chromaticity { x: 15000, y: 6000 },
+            }),
+            StandardChunk::Srgb(RenderingIntent::Saturation),
+            StandardChunk::PhysicalDimensions(PhysicalDimensions {
+                x: 2835,
+                y: 2835,
+                unit: PhysicalUnit::Meter,
+            }),
+            StandardChunk::SignificantBits(vec![5, 6, 5]),
+            StandardChunk::Background(Color::Rgb {
+                red: 255,
+                green: 255,
+                blue: 255,
+            }),
+            StandardChunk::Histogram(vec![1, 2, 3]),
+            StandardChunk::Time(Time {
+                year: 2024,
+                month: 2,
+                day: 29,
+                hour: 23,
+                minute: 59,
+                second: 60,
+            }),
+        ];
+
+        for chunk in chunks {
+            assert_round_trip(chunk, &rgb);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_suggested_palette() {
+        for depth in [8, 16] {
+            let chunk = StandardChunk::SuggestedPalette(SuggestedPalette {
+                name: "web safe".to_string(),
+                depth,
+                entries: vec![SuggestedEntry {
+                    red: 51,
+                    green: 102,
+                    blue: 153,
+                    alpha: 255,
+                    frequency: 1000,
+                }],
+            });
+            assert_round_trip(chunk, &ihdr(ColorType::Rgb, 8));
+        }
+    }
+
+    #[test]
+    fn test_layout_depends_on_color_type() {
+        let chunk = Chunk::new("bKGD".parse().unwrap(), vec![0, 7]);
+
+        assert_eq!(
+            StandardChunk::parse(&chunk, &ihdr(ColorType::GrayscaleAlpha, 8)).unwrap(),
+            StandardChunk::Background(Color::Gray(7))
+        );
+        assert!(StandardChunk::parse(&chunk, &ihdr(ColorType::Rgb, 8)).is_err());
+
+        let chunk = Chunk::new("tRNS".parse().unwrap(), vec![0, 128]);
+        assert_eq!(
+            StandardChunk::parse(&chunk, &ihdr(ColorType::Indexed, 8)).unwrap(),
+            StandardChunk::Transparency(Transparency::Alphas(vec![0, 128]))
+        );
+        assert!(StandardChunk::parse(&chunk, &ihdr(ColorType::Rgba, 8)).is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let rgb = ihdr(ColorType::Rgb, 8);
+        let parse = |chunk_type: &str, data: &[u8]| {
+            StandardChunk::parse(
+                &Chunk::new(chunk_type.parse().unwrap(), data.to_vec()),
+                &rgb,
+            )
+        };
+
+        assert!(parse("PLTE", &[1, 2, 3, 4]).is_err());
+        assert!(parse("gAMA", &[0, 0, 1]).is_err());
+        assert!(parse("sRGB", &[4]).is_err());
+        assert!(parse("pHYs", &[0, 0, 0, 1, 0, 0, 0, 1, 2]).is_err());
+        assert!(parse("sPLT", b"name\x0012").is_err());
+        assert!(parse("sPLT", b"no separator").is_err());
+        assert!(parse("tIME", &[7, 232, 1, 1]).is_err());
+        assert!(parse("ruSt", &[]).is_err());
+    }
+
+    #[test]
+    fn test_validate_samples() {
+        let gray = ihdr(ColorType::Grayscale, 4);
+
+        assert!(StandardChunk::Background(Color::Gray(15))
+            .validate(&gray, None)
+            .is_ok());
+        assert!(StandardChunk::Background(Color::Gray(16))
+            .validate(&gray, None)
+            .is_err());
+        assert!(StandardChunk::SignificantBits(vec![5])
+            .validate(&gray, None)
+            .is_err());
+        assert!(StandardChunk::Palette(palette(3))
+            .validate(&gray, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_against_palette() {
+        let indexed = ihdr(ColorType::Indexed, 2);
+        let entries = palette(3);
+
+        assert!(StandardChunk::Palette(entries.clone())
+            .validate(&indexed, None)
+            .is_ok());
+        assert!(StandardChunk::Palette(palette(5))
+            .validate(&indexed, None)
+            .is_err());
+        assert!(StandardChunk::Background(Color::Index(2))
+            .validate(&indexed, Some(&entries))
+            .is_ok());
+        assert!(StandardChunk::Background(Color::Index(3))
+            .validate(&indexed, Some(&entries))
+            .is_err());
+        assert!(StandardChunk::Histogram(vec![1, 2])
+            .validate(&indexed, Some(&entries))
+            .is_err());
+        assert!(
+            StandardChunk::Transparency(Transparency::Alphas(vec![0; 4]))
+                .validate(&indexed, Some(&entries))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_validate_time() {
+        let time = Time {
+            year: 2024,
+            month: 13,
+            day: 1,
+            hour: 0,
+            minute: 0,
+            second: 0,
+        };
+
+        assert!(StandardChunk::Time(time)
+            .validate(&ihdr(ColorType::Rgb, 8), None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        let dimensions = StandardChunk::PhysicalDimensions(PhysicalDimensions {
+            x: 3780,
+            y: 3780,
+            unit: PhysicalUnit::Meter,
+        });
+        assert_eq!(
+            dimensions.to_string(),
+            "3780 x 3780 pixels per meter (96 x 96 DPI)"
+        );
+
+        assert_eq!(StandardChunk::Gamma(45455).to_string(), "0.45455");
+        assert_eq!(
+            StandardChunk::Palette(palette(10)).to_string(),
+            "10 entries #000000 #010101 #020202 #030303 #040404 #050505 #060606 #070707 ..."
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb, 8).to_chunk(),
+            Chunk::new("gAMA".parse().unwrap(), vec![0, 0, 0, 0]),
+            Chunk::new("sRGB".parse().unwrap(), vec![0]),
+            Chunk::new("IDAT".parse().unwrap(), vec![]),
+            Chunk::new("IEND".parse().unwrap(), vec![]),
+        ]);
+
+        let list = list(&png).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].0, 1);
+        assert!(list[0].1.is_err());
+        assert_eq!(
+            list[1].1.as_ref().unwrap(),
+            &StandardChunk::Srgb(RenderingIntent::Perceptual)
+        );
+    }
+
+    fn chunk(chunk_type: &str) -> Chunk {
+        Chunk::new(chunk_type.parse().unwrap(), vec![])
+    }
+
+    fn types(png: &Png) -> Vec<String> {
+        png.chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect()
+    }
+
+    fn indexed_png() -> Png {
+        let mut chunks = vec![ihdr(ColorType::Indexed, 8).to_chunk()];
+        chunks.extend(["tEXt", "PLTE", "tRNS", "IDAT", "IDAT", "IEND"].map(chunk));
+        chunks[2] = StandardChunk::Palette(palette(4)).to_chunk().unwrap();
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_set_places_chunks() {
+        let mut png = indexed_png();
+
+        set(&mut png, &StandardChunk::Time(Time::from_unix(0).unwrap())).unwrap();
+        set(&mut png, &StandardChunk::Gamma(45455)).unwrap();
+        set(&mut png, &StandardChunk::Background(Color::Index(3))).unwrap();
+        set(
+            &mut png,
+            &StandardChunk::PhysicalDimensions(PhysicalDimensions::from_dpi(72.0, 72.0).unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            types(&png),
+            [
+                "IHDR", "tEXt", "gAMA", "PLTE", "tRNS", "bKGD", "pHYs", "IDAT", "IDAT", "tIME",
+                "IEND"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_replaces() {
+        let mut png = indexed_png();
+        png.insert_chunk(
+            StandardChunk::Gamma(1).to_chunk().unwrap(),
+            Position::BeforeIend,
+        )
+        .unwrap();
+
+        let index = set(&mut png, &StandardChunk::Gamma(45455)).unwrap();
+
+        assert_eq!(index, 2);
+        assert_eq!(png.chunks_by_type("gAMA").count(), 1);
+        assert_eq!(png.chunks()[2].data(), 45455u32.to_be_bytes());
+    }
+
+    #[test]
+    fn test_set_validates() {
+        let mut png = indexed_png();
+
+        assert!(set(&mut png, &StandardChunk::Background(Color::Index(4))).is_err());
+        assert!(set(&mut png, &StandardChunk::Gamma(0)).is_err());
+        assert_eq!(png.chunks().len(), 7);
+    }
+
+    #[test]
+    fn test_time_from_unix() {
+        let time = Time::from_unix(951_782_400 + 3_723).unwrap();
+        assert_eq!(time.to_string(), "2000-02-29 01:02:03 UTC");
+        assert_eq!(
+            Time::from_unix(0).unwrap().to_string(),
+            "1970-01-01 00:00:00 UTC"
+        );
+    }
+
+    #[test]
+    fn test_time_from_str() {
+        let time: Time = "2024-12-31T23:59:60Z".parse().unwrap();
+        assert_eq!(time.to_string(), "2024-12-31 23:59:60 UTC");
+
+        assert!("2024-12-31".parse::<Time>().is_err());
+        assert!("2024-13-01 00:00:00".parse::<Time>().is_err());
+        assert!("2023-02-29 00:00:00".parse::<Time>().is_err());
+        assert!("2024-02-29 00:00:00".parse::<Time>().is_ok());
+        assert!("2024-1-1 0:0:300".parse::<Time>().is_err());
+    }
+
+    #[test]
+    fn test_chromaticities_from_str() {
+        assert_eq!(
+            "srgb".parse::<Chromaticities>().unwrap(),
+            Chromaticities::SRGB
+        );
+        assert_eq!(
+            "0.3127,0.329,0.64,0.33,0.3,0.6,0.15,0.06"
+                .parse::<Chromaticities>()
+                .unwrap(),
+            Chromaticities::SRGB
+        );
+        assert!("0.3127,0.329".parse::<Chromaticities>().is_err());
+        assert!("-1,0,0,0,0,0,0,0".parse::<Chromaticities>().is_err());
+    }
+
+    #[test]
+    fn test_color_parse_samples() {
+        assert_eq!(
+            Color::parse_samples("#ff8000", ColorType::Rgba).unwrap(),
+            Color::Rgb {
+                red: 255,
+                green: 128,
+                blue: 0
+            }
+        );
+        assert_eq!(
+            Color::parse_samples("1000", ColorType::Grayscale).unwrap(),
+            Color::Gray(1000)
+        );
+        assert_eq!(
+            Color::parse_samples("7", ColorType::Indexed).unwrap(),
+            Color::Index(7)
+        );
+        assert!(Color::parse_samples("1, 2", ColorType::Rgb).is_err());
+        assert!(Color::parse_samples("#fff", ColorType::Rgb).is_err());
+        assert!(Color::parse_samples("256", ColorType::Indexed).is_err());
+    }
+
+    #[test]
+    fn test_from_dpi() {
+        let dimensions = PhysicalDimensions::from_dpi(300.0, 150.0).unwrap();
+        assert_eq!((dimensions.x, dimensions.y), (11811, 5906));
+
+        assert!(PhysicalDimensions::from_dpi(f64::INFINITY, 72.0).is_err());
+        assert!(PhysicalDimensions::from_dpi(72.0, 1e9).is_err());
+        assert!(PhysicalDimensions::from_dpi(0.001, 72.0).is_err());
+    }
+
+    #[test]
+    fn test_fixed_point() {
+        assert_eq!(fixed_point("0.45455").unwrap(), 45455);
+        assert!(fixed_point("inf").is_err());
+        assert!(fixed_point("1e6").is_err());
+        assert!(fixed_point("-1").is_err());
+    }
+}
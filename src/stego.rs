@@ -0,0 +1,460 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+
+use crate::{
+    idat::{self, Scanlines},
+    ihdr::{ColorType, Ihdr},
+    png::Png,
+};
+
+/// Marks pixel data as carrying a message
+pub const MAGIC: [u8; 4] = *b"PNGl";
+
+/// Magic and message length
+pub const HEADER_LENGTH: usize = 8;
+
+/// The color channels whose samples carry message bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Channels {
+    pub red: bool,
+    pub green: bool,
+    pub blue: bool,
+    pub alpha: bool,
+}
+
+impl Default for Channels {
+    fn default() -> Self {
+        Self {
+            red: true,
+            green: true,
+            blue: true,
+            alpha: false,
+        }
+    }
+}
+
+impl FromStr for Channels {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut channels = Self {
+            red: false,
+            green: false,
+            blue: false,
+            alpha: false,
+        };
+
+        for c in s.chars() {
+            match c.to_ascii_lowercase() {
+                'r' => channels.red = true,
+                'g' => channels.green = true,
+                'b' => channels.blue = true,
+                'a' => channels.alpha = true,
+                _ => bail!("invalid channel `{c}`, expected any of `rgba`"),
+            }
+        }
+
+        if s.is_empty() {
+            bail!("at least one channel is required");
+        }
+
+        Ok(channels)
+    }
+}
+
+impl Display for Channels {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (enabled, name) in [
+            (self.red, 'r'),
+            (self.green, 'g'),
+            (self.blue, 'b'),
+            (self.alpha, 'a'),
+        ] {
+            if enabled {
+                write!(f, "{name}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Channels {
+    /// Byte offsets inside a pixel of the low bytes of the selected samples
+    ///
+    /// The gray sample of grayscale images counts as any of red, green and blue.
+    fn offsets(&self, ihdr: &Ihdr) -> Result<Vec<usize>> {
+        if ihdr.color_type == ColorType::Indexed || ihdr.bit_depth < 8 {
+            bail!("LSB embedding needs 8 or 16 bit samples and doesn't work on indexed images");
+        }
+
+        let color = self.red || self.green || self.blue;
+        let selected = match ihdr.color_type {
+            ColorType::Grayscale => vec![color],
+            ColorType::GrayscaleAlpha => vec![color, self.alpha],
+            ColorType::Rgb => vec![self.red, self.green, self.blue],
+            ColorType::Rgba => vec![self.red, self.green, self.blue, self.alpha],
+            ColorType::Indexed => unreachable!(),
+        };
+
+        let sample_length = usize::from(ihdr.bit_depth / 8);
+        let offsets: Vec<_> = selected
+            .iter()
+            .enumerate()
+            .filter(|(_, selected)| **selected)
+            .map(|(sample, _)| sample * sample_length + sample_length - 1)
+            .collect();
+
+        if offsets.is_empty() {
+            bail!(
+                "none of the channels {self} exist in a {} image",
+                ihdr.color_type
+            );
+        }
+
+        Ok(offsets)
+    }
+}
+
+/// Where and how densely message bits are stored in the pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LsbOptions {
+    pub channels: Channels,
+    /// Low bits of every selected sample to use, 1 to 8
+    pub bits: u8,
+}
+
+impl Default for LsbOptions {
+    fn default() -> Self {
+        Self {
+            channels: Channels::default(),
+            bits: 1,
+        }
+    }
+}
+
+impl LsbOptions {
+    /// Indices into the scanline data of every byte carrying message bits, in order
+    fn positions(&self, ihdr: &Ihdr) -> Result<impl Iterator<Item = usize>> {
+        if !(1..=8).contains(&self.bits) {
+            bail!("bits per sample must be between 1 and 8, not {}", self.bits);
+        }
+
+        let offsets = self.channels.offsets(ihdr)?;
+        let pixel_length = ihdr.bits_per_pixel() / 8;
+        let pixels = ihdr.width as usize * ihdr.height as usize;
+
+        Ok((0..pixels).flat_map(move |pixel| {
+            let start = pixel * pixel_length;
+            offsets
+                .clone()
+                .into_iter()
+                .map(move |offset| start + offset)
+        }))
+    }
+
+    /// Message bytes that fit in an image described by `ihdr`
+    pub fn capacity(&self, ihdr: &Ihdr) -> Result<usize> {
+        let bits = self.positions(ihdr)?.count() * usize::from(self.bits);
+
+        Ok((bits / 8).saturating_sub(HEADER_LENGTH))
+    }
+}
+
+/// Hides `message` in the low bits of the pixels of `png`
+///
+/// The pixels hold `MAGIC | length | message`, the image data is filtered and
+/// compressed again afterwards.
+pub fn embed(png: &mut Png, message: &[u8], options: &LsbOptions) -> Result<()> {
+    let mut scanlines = Scanlines::read(png)?;
+
+    let capacity = options.capacity(&scanlines.ihdr)?;
+    if message.len() > capacity {
+        bail!(
+            "message needs {} bytes but the image only holds {capacity}",
+            message.len()
+        );
+    }
+
+    let mut data = MAGIC.to_vec();
+    data.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    data.extend_from_slice(message);
+
+    let bits = usize::from(options.bits);
+    let mask = ((1u16 << bits) - 1) as u8;
+    let mut reader = BitReader::new(&data);
+
+    for position in options.positions(&scanlines.ihdr)? {
+        let Some(value) = reader.read(bits) else {
+            break;
+        };
+
+        let byte = &mut scanlines.data[position];
+        *byte = (*byte & !mask) | value;
+    }
+
+    idat::replace_image_data(
+        png,
+        &scanlines.to_zlib(idat::DEFAULT_LEVEL)?,
+        idat::DEFAULT_CHUNK_SIZE,
+    )
+}
+
+/// Reads a message hidden by [`embed`] with the same `options`
+pub fn extract(png: &Png, options: &LsbOptions) -> Result<Vec<u8>> {
+    let scanlines = Scanlines::read(png)?;
+
+    let bits = usize::from(options.bits);
+    let mask = ((1u16 << bits) - 1) as u8;
+    let mut writer = BitWriter::default();
+    let mut length = None;
+
+    for position in options.positions(&scanlines.ihdr)? {
+        writer.write(scanlines.data[position] & mask, bits);
+
+        if length.is_none() && writer.bytes.len() >= HEADER_LENGTH {
+            if writer.bytes[..MAGIC.len()] != MAGIC {
+                bail!("No message found in the pixel data");
+            }
+
+            let header: [u8; 4] = writer.bytes[MAGIC.len()..HEADER_LENGTH].try_into()?;
+            length = Some(HEADER_LENGTH + u32::from_be_bytes(header) as usize);
+        }
+
+        if length.is_some_and(|l| writer.bytes.len() >= l) {
+            break;
+        }
+    }
+
+    match length {
+        Some(length) if writer.bytes.len() >= length => {
+            Ok(writer.bytes[HEADER_LENGTH..length].to_vec())
+        }
+        Some(_) => bail!("message in the pixel data is truncated"),
+        None => bail!("No message found in the pixel data"),
+    }
+}
+
+/// Reads bits most significant first
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    bit: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, bit: 0 }
+    }
+
+    /// The next `count` bits, padded with zeros at the end, `None` once all were read
+    fn read(&mut self, count: usize) -> Option<u8> {
+        if self.bit >= self.bytes.len() * 8 {
+            return None;
+        }
+
+        let mut value = 0;
+        for _ in 0..count {
+            let bit = self
+                .bytes
+                .get(self.bit / 8)
+                .map_or(0, |b| (b >> (7 - self.bit % 8)) & 1);
+            value = (value << 1) | bit;
+            self.bit += 1;
+        }
+
+        Some(value)
+    }
+}
+
+/// Collects bits most significant first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u8,
+    filled: usize,
+}
+
+impl BitWriter {
+    /// Appends the low `count` bits of `value`
+    fn write(&mut self, value: u8, count: usize) {
+        for i in (0..count).rev() {
+            self.current = (self.current << 1) | ((value >> i) & 1);
+            self.filled += 1;
+
+            if self.filled == 8 {
+                self.bytes.push(self.current);
+                self.current = 0;
+                self.filled = 0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chunk::Chunk,
+        idat::{FilterStrategy, RawImage},
+        ihdr::InterlaceMethod,
+        zlib,
+    };
+    use flate2::Compression;
+
+    fn ihdr(width: u32, height: u32, bit_depth: u8, color_type: ColorType) -> Ihdr {
+        Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+    }
+
+    /// An image with every row stored with the Sub filter
+    fn testing_png(ihdr: &Ihdr) -> Png {
+        let row_length = ihdr.bytes_per_row();
+        let mut filtered = vec![];
+        for y in 0..ihdr.height as usize {
+            filtered.push(1);
+            filtered.extend((0..row_length).map(|x| (x * 7 + y * 13) as u8));
+        }
+
+        Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new(
+                "IDAT".parse().unwrap(),
+                zlib::compress(&filtered, Compression::default()).unwrap(),
+            ),
+            Chunk::new("IEND".parse().unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_channels_from_str() {
+        let channels: Channels = "rB".parse().unwrap();
+        assert!(channels.red && channels.blue);
+        assert!(!channels.green && !channels.alpha);
+        assert_eq!(channels.to_string(), "rb");
+
+        assert!("".parse::<Channels>().is_err());
+        assert!("rgbx".parse::<Channels>().is_err());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let options = LsbOptions::default();
+        // 100 pixels with 3 bits each, minus the header
+        let capacity = options.capacity(&ihdr(10, 10, 8, ColorType::Rgba));
+        assert_eq!(capacity.unwrap(), 37 - HEADER_LENGTH);
+
+        let options = LsbOptions {
+            channels: "rgba".parse().unwrap(),
+            bits: 2,
+        };
+        let capacity = options.capacity(&ihdr(10, 10, 16, ColorType::Rgba));
+        assert_eq!(capacity.unwrap(), 100 - HEADER_LENGTH);
+    }
+
+    #[test]
+    fn test_capacity_of_unsupported_images() {
+        let options = LsbOptions::default();
+        assert!(options
+            .capacity(&ihdr(10, 10, 8, ColorType::Indexed))
+            .is_err());
+        assert!(options
+            .capacity(&ihdr(10, 10, 4, ColorType::Grayscale))
+            .is_err());
+
+        let options = LsbOptions {
+            channels: "a".parse().unwrap(),
+            bits: 1,
+        };
+        assert!(options.capacity(&ihdr(10, 10, 8, ColorType::Rgb)).is_err());
+    }
+
+    #[test]
+    fn test_embed_and_extract() {
+        let mut png = testing_png(&ihdr(8, 8, 8, ColorType::Rgb));
+        let before = Scanlines::read(&png).unwrap();
+
+        embed(&mut png, b"hidden", &LsbOptions::default()).unwrap();
+        assert_eq!(extract(&png, &LsbOptions::default()).unwrap(), b"hidden");
+
+        // only the lowest bit of a sample may change
+        let after = Scanlines::read(&png).unwrap();
+        assert_eq!(after.filters, before.filters);
+        for (a, b) in after.data.iter().zip(&before.data) {
+            assert!(a ^ b <= 1);
+        }
+    }
+
+    #[test]
+    fn test_embed_and_extract_with_options() {
+        let options = LsbOptions {
+            channels: "ga".parse().unwrap(),
+            bits: 3,
+        };
+
+        for (bit_depth, color_type) in [
+            (8, ColorType::GrayscaleAlpha),
+            (16, ColorType::Rgba),
+            (16, ColorType::Grayscale),
+        ] {
+            let mut png = testing_png(&ihdr(9, 7, bit_depth, color_type));
+            embed(&mut png, b"another secret", &options).unwrap();
+
+            assert_eq!(extract(&png, &options).unwrap(), b"another secret");
+        }
+    }
+
+    #[test]
+    fn test_embed_and_extract_interlaced() {
+        let ihdr = Ihdr {
+            interlace_method: InterlaceMethod::Adam7,
+            ..ihdr(10, 10, 8, ColorType::Rgba)
+        };
+        let image = RawImage {
+            ihdr,
+            pixels: (0..400).map(|i| i as u8).collect(),
+        };
+        let data = image.encode(FilterStrategy::Adaptive, 6).unwrap();
+        let mut png = Png::from_chunks(vec![
+            ihdr.to_chunk(),
+            Chunk::new("IDAT".parse().unwrap(), data),
+            Chunk::new("IEND".parse().unwrap(), vec![]),
+        ]);
+
+        embed(&mut png, b"interlaced", &LsbOptions::default()).unwrap();
+        assert_eq!(
+            extract(&png, &LsbOptions::default()).unwrap(),
+            b"interlaced"
+        );
+
+        let after = RawImage::decode(&png).unwrap();
+        for (a, b) in after.pixels.iter().zip(&image.pixels) {
+            assert!(a ^ b <= 1);
+        }
+    }
+
+    #[test]
+    fn test_embed_too_large() {
+        let mut png = testing_png(&ihdr(4, 4, 8, ColorType::Rgb));
+        let capacity = LsbOptions::default()
+            .capacity(&png.ihdr().unwrap())
+            .unwrap();
+
+        let message = vec![b'x'; capacity + 1];
+        assert!(embed(&mut png, &message, &LsbOptions::default()).is_err());
+        assert!(embed(&mut png, &message[1..], &LsbOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_extract_without_message() {
+        let png = testing_png(&ihdr(8, 8, 8, ColorType::Rgb));
+        assert!(extract(&png, &LsbOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+
+use crate::{chunk::Chunk, chunk_type::ChunkType, png::Png, validate::KNOWN_CRITICAL};
+
+/// Selects chunks by their type or by the property bits of the type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChunkPattern {
+    /// A chunk type where `*` matches any number of letters and `?` a single one
+    Type(String),
+    Ancillary,
+    Critical,
+    Private,
+    SafeToCopy,
+    UnsafeToCopy,
+}
+
+impl ChunkPattern {
+    pub fn matches(&self, chunk_type: &ChunkType) -> bool {
+        match self {
+            ChunkPattern::Type(pattern) => glob_matches(pattern.as_bytes(), &chunk_type.bytes()),
+            ChunkPattern::Ancillary => !chunk_type.is_critical(),
+            ChunkPattern::Critical => chunk_type.is_critical(),
+            ChunkPattern::Private => !chunk_type.is_public(),
+            ChunkPattern::SafeToCopy => chunk_type.is_safe_to_copy(),
+            ChunkPattern::UnsafeToCopy => !chunk_type.is_safe_to_copy(),
+        }
+    }
+}
+
+impl FromStr for ChunkPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "ancillary" => ChunkPattern::Ancillary,
+            "critical" => ChunkPattern::Critical,
+            "private" => ChunkPattern::Private,
+            "safe-to-copy" => ChunkPattern::SafeToCopy,
+            "unsafe-to-copy" => ChunkPattern::UnsafeToCopy,
+            _ => {
+                if !s
+                    .chars()
+                    .all(|c| c.is_ascii_alphabetic() || c == '*' || c == '?')
+                {
+                    bail!("invalid chunk pattern `{s}`, expected a chunk type with `*` and `?` or a chunk property");
+                }
+
+                ChunkPattern::Type(s.to_string())
+            }
+        })
+    }
+}
+
+impl Display for ChunkPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChunkPattern::Type(pattern) => write!(f, "{pattern}"),
+            ChunkPattern::Ancillary => write!(f, "ancillary"),
+            ChunkPattern::Critical => write!(f, "critical"),
+            ChunkPattern::Private => write!(f, "private"),
+            ChunkPattern::SafeToCopy => write!(f, "safe-to-copy"),
+            ChunkPattern::UnsafeToCopy => write!(f, "unsafe-to-copy"),
+        }
+    }
+}
+
+/// Matches chunks by type, content and size, every condition given has to hold
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChunkFilter {
+    pub pattern: Option<ChunkPattern>,
+    /// Bytes the chunk data has to contain
+    pub contains: Option<Vec<u8>>,
+    /// Smallest data length to match
+    pub min_size: Option<usize>,
+    /// Largest data length to match
+    pub max_size: Option<usize>,
+}
+
+impl ChunkFilter {
+    pub fn matches(&self, chunk: &Chunk) -> bool {
+        let data = chunk.data();
+
+        self.pattern
+            .as_ref()
+            .is_none_or(|p| p.matches(chunk.chunk_type()))
+            && self
+                .contains
+                .as_ref()
+                .is_none_or(|c| c.is_empty() || data.windows(c.len()).any(|w| w == c))
+            && self.min_size.is_none_or(|min| data.len() >= min)
+            && self.max_size.is_none_or(|max| data.len() <= max)
+    }
+
+    /// Whether `chunk` matches and may be removed by the filter
+    ///
+    /// Like [`StripOptions::drops`], the critical chunks of the spec are never
+    /// removed, they have to be picked by index.
+    pub fn removes(&self, chunk: &Chunk) -> bool {
+        !KNOWN_CRITICAL.contains(&chunk.chunk_type().to_string().as_str()) && self.matches(chunk)
+    }
+}
+
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.split_first(), text.split_first()) {
+        (None, _) => text.is_empty(),
+        (Some((b'*', rest)), _) => {
+            glob_matches(rest, text) || (!text.is_empty() && glob_matches(pattern, &text[1..]))
+        }
+        (Some((b'?', rest)), Some((_, text))) => glob_matches(rest, text),
+        (Some((p, rest)), Some((t, text))) => p == t && glob_matches(rest, text),
+        (Some(_), None) => false,
+    }
+}
+
+/// Which chunks [`strip`] removes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StripOptions {
+    /// Chunks to keep even if they would be dropped
+    pub keep: Vec<ChunkPattern>,
+    /// Chunks to drop, all ancillary chunks if empty
+    pub drop: Vec<ChunkPattern>,
+}
+
+impl StripOptions {
+    /// Whether a chunk of `chunk_type` gets removed
+    ///
+    /// The critical chunks of the spec are never removed, as the image would be
+    /// unreadable without them.
+    pub fn drops(&self, chunk_type: &ChunkType) -> bool {
+        if KNOWN_CRITICAL.contains(&chunk_type.to_string().as_str()) {
+            return false;
+        }
+
+        if self.keep.iter().any(|p| p.matches(chunk_type)) {
+            return false;
+        }
+
+        match self.drop.is_empty() {
+            true => !chunk_type.is_critical(),
+            false => self.drop.iter().any(|p| p.matches(chunk_type)),
+        }
+    }
+}
+
+/// Removes the chunks `options` drops and returns them
+pub fn strip(png: &mut Png, options: &StripOptions) -> Vec<Chunk> {
+    png.retain(|chunk| !options.drops(chunk.chunk_type()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str) -> Chunk {
+        Chunk::new(chunk_type.parse().unwrap(), vec![])
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(
+            [
+                "IHDR", "gAMA", "tEXt", "zTXt", "IDAT", "ruSt", "RuSt", "eXIf", "IEND",
+            ]
+            .into_iter()
+            .map(chunk)
+            .collect(),
+        )
+    }
+
+    fn types(chunks: &[Chunk]) -> Vec<String> {
+        chunks.iter().map(|c| c.chunk_type().to_string()).collect()
+    }
+
+    fn patterns(patterns: &[&str]) -> Vec<ChunkPattern> {
+        patterns.iter().map(|p| p.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches(b"tEXt", b"tEXt"));
+        assert!(!glob_matches(b"tEXt", b"zTXt"));
+        assert!(glob_matches(b"?TXt", b"zTXt"));
+        assert!(glob_matches(b"*", b"IDAT"));
+        assert!(glob_matches(b"*t", b"ruSt"));
+        assert!(glob_matches(b"r*S?", b"ruSt"));
+        assert!(!glob_matches(b"r*", b"RuSt"));
+        assert!(!glob_matches(b"???", b"IDAT"));
+    }
+
+    #[test]
+    fn test_pattern_from_str() {
+        assert_eq!(
+            "t*".parse::<ChunkPattern>().unwrap(),
+            ChunkPattern::Type("t*".to_string())
+        );
+        assert_eq!(
+            "unsafe-to-copy".parse::<ChunkPattern>().unwrap(),
+            ChunkPattern::UnsafeToCopy
+        );
+        assert!("t.xt".parse::<ChunkPattern>().is_err());
+    }
+
+    #[test]
+    fn test_pattern_matches_property_bits() {
+        let rust: ChunkType = "ruSt".parse().unwrap();
+        assert!(ChunkPattern::Ancillary.matches(&rust));
+        assert!(ChunkPattern::Private.matches(&rust));
+        assert!(ChunkPattern::SafeToCopy.matches(&rust));
+        assert!(!ChunkPattern::Critical.matches(&rust));
+        assert!(!ChunkPattern::UnsafeToCopy.matches(&rust));
+    }
+
+    #[test]
+    fn test_chunk_filter() {
+        let chunk = Chunk::new("ruSt".parse().unwrap(), b"a secret message".to_vec());
+        let filter = |pattern: Option<&str>, contains: Option<&str>, min, max| ChunkFilter {
+            pattern: pattern.map(|p| p.parse().unwrap()),
+            contains: contains.map(|c| c.as_bytes().to_vec()),
+            min_size: min,
+            max_size: max,
+        };
+
+        assert!(ChunkFilter::default().matches(&chunk));
+        assert!(filter(Some("r*"), Some("secret"), Some(16), Some(16)).matches(&chunk));
+        assert!(!filter(Some("R*"), None, None, None).matches(&chunk));
+        assert!(!filter(None, Some("public"), None, None).matches(&chunk));
+        assert!(!filter(None, None, Some(17), None).matches(&chunk));
+        assert!(!filter(None, None, None, Some(15)).matches(&chunk));
+    }
+
+    #[test]
+    fn test_chunk_filter_never_removes_known_critical() {
+        let filter = ChunkFilter {
+            min_size: Some(0),
+            ..Default::default()
+        };
+        let mut png = testing_png();
+        let removed = png.remove_where(|c| filter.removes(c));
+
+        assert_eq!(
+            types(&removed),
+            ["gAMA", "tEXt", "zTXt", "ruSt", "RuSt", "eXIf"]
+        );
+    }
+
+    #[test]
+    fn test_strip_ancillary() {
+        let mut png = testing_png();
+        let removed = strip(&mut png, &StripOptions::default());
+
+        assert_eq!(types(&removed), ["gAMA", "tEXt", "zTXt", "ruSt", "eXIf"]);
+        assert_eq!(types(png.chunks()), ["IHDR", "IDAT", "RuSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_strip_with_keep() {
+        let mut png = testing_png();
+        let options = StripOptions {
+            keep: patterns(&["gAMA", "?TXt"]),
+            drop: vec![],
+        };
+        strip(&mut png, &options);
+
+        assert_eq!(
+            types(png.chunks()),
+            ["IHDR", "gAMA", "zTXt", "IDAT", "RuSt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_strip_with_drop() {
+        let mut png = testing_png();
+        let options = StripOptions {
+            keep: patterns(&["tEXt"]),
+            drop: patterns(&["private", "t*", "IDAT"]),
+        };
+        let removed = strip(&mut png, &options);
+
+        assert_eq!(types(&removed), ["ruSt", "RuSt"]);
+    }
+
+    #[test]
+    fn test_strip_never_drops_known_critical() {
+        let mut png = testing_png();
+        let options = StripOptions {
+            keep: vec![],
+            drop: patterns(&["*"]),
+        };
+        strip(&mut png, &options);
+
+        assert_eq!(types(png.chunks()), ["IHDR", "IDAT", "IEND"]);
+    }
+}
@@ -0,0 +1,471 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+use flate2::Compression;
+
+use crate::{
+    chunk::Chunk,
+    chunk_type::ChunkType,
+    png::{Png, Position},
+    zlib,
+};
+
+/// Largest text a compressed chunk may inflate to
+const MAX_TEXT_LENGTH: usize = 16 * 1024 * 1024;
+
+/// A textual metadata chunk, i.e. `tEXt`, `zTXt` or `iTXt`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TextChunk {
+    /// `tEXt`, Latin-1 text
+    Text { keyword: String, text: String },
+    /// `zTXt`, zlib compressed Latin-1 text
+    CompressedText { keyword: String, text: String },
+    /// `iTXt`, optionally compressed UTF-8 text
+    InternationalText {
+        keyword: String,
+        compressed: bool,
+        language_tag: String,
+        translated_keyword: String,
+        text: String,
+    },
+}
+
+impl TextChunk {
+    pub const CHUNK_TYPES: [&'static str; 3] = ["tEXt", "zTXt", "iTXt"];
+
+    pub fn keyword(&self) -> &str {
+        match self {
+            TextChunk::Text { keyword, .. }
+            | TextChunk::CompressedText { keyword, .. }
+            | TextChunk::InternationalText { keyword, .. } => keyword,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            TextChunk::Text { text, .. }
+            | TextChunk::CompressedText { text, .. }
+            | TextChunk::InternationalText { text, .. } => text,
+        }
+    }
+
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            TextChunk::Text { .. } => "tEXt",
+            TextChunk::CompressedText { .. } => "zTXt",
+            TextChunk::InternationalText { .. } => "iTXt",
+        }
+    }
+
+    pub fn is_text_chunk(chunk: &Chunk) -> bool {
+        Self::CHUNK_TYPES.contains(&chunk.chunk_type().to_string().as_str())
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let mut data = keyword_to_latin1(self.keyword())?;
+        data.push(0);
+
+        match self {
+            TextChunk::Text { text, .. } => data.extend(to_latin1(text)?),
+            TextChunk::CompressedText { text, .. } => {
+                data.push(0);
+                data.extend(zlib::compress(&to_latin1(text)?, Compression::default())?);
+            }
+            TextChunk::InternationalText {
+                compressed,
+                language_tag,
+                translated_keyword,
+                text,
+                ..
+            } => {
+                if !language_tag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+                {
+                    bail!("invalid language tag {language_tag}");
+                }
+
+                if translated_keyword.contains('\0') {
+                    bail!("translated keyword must not contain null bytes");
+                }
+
+                data.push(u8::from(*compressed));
+                data.push(0);
+                data.extend(language_tag.as_bytes());
+                data.push(0);
+                data.extend(translated_keyword.as_bytes());
+                data.push(0);
+
+                match compressed {
+                    true => data.extend(zlib::compress(text.as_bytes(), Compression::default())?),
+                    false => data.extend(text.as_bytes()),
+                }
+            }
+        }
+
+        Chunk::try_new(ChunkType::from_str(self.chunk_type())?, data)
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = Error;
+
+    fn try_from(value: &Chunk) -> Result<Self, Self::Error> {
+        let chunk_type = value.chunk_type().to_string();
+        let (keyword, rest) = split_at_null(value.data())?;
+
+        if keyword.is_empty() || keyword.len() > 79 {
+            bail!("keyword has to be 1 to 79 bytes long");
+        }
+
+        let keyword = from_latin1(keyword);
+
+        match chunk_type.as_str() {
+            "tEXt" => Ok(TextChunk::Text {
+                keyword,
+                text: from_latin1(rest),
+            }),
+            "zTXt" => {
+                let Some((&method, text)) = rest.split_first() else {
+                    bail!("missing compression method");
+                };
+
+                if method != 0 {
+                    bail!("invalid compression method {method}");
+                }
+
+                Ok(TextChunk::CompressedText {
+                    keyword,
+                    text: from_latin1(&zlib::decompress(text, MAX_TEXT_LENGTH)?),
+                })
+            }
+            "iTXt" => {
+                let [flag, method, rest @ ..] = rest else {
+                    bail!("missing compression flag");
+                };
+
+                let compressed = match (flag, method) {
+                    (0, _) => false,
+                    (1, 0) => true,
+                    (1, method) => bail!("invalid compression method {method}"),
+                    (flag, _) => bail!("invalid compression flag {flag}"),
+                };
+
+                let (language_tag, rest) = split_at_null(rest)?;
+                let (translated_keyword, text) = split_at_null(rest)?;
+
+                let text = match compressed {
+                    true => zlib::decompress(text, MAX_TEXT_LENGTH)?,
+                    false => text.to_vec(),
+                };
+
+                Ok(TextChunk::InternationalText {
+                    keyword,
+                    compressed,
+                    language_tag: String::from_utf8(language_tag.to_vec())?,
+                    translated_keyword: String::from_utf8(translated_keyword.to_vec())?,
+                    text: String::from_utf8(text)?,
+                })
+            }
+            _ => bail!("{chunk_type} is not a text chunk"),
+        }
+    }
+}
+
+impl Display for TextChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.keyword(), self.text())
+    }
+}
+
+/// Returns all text chunks of `png` with their chunk index
+pub fn list(png: &Png) -> Vec<(usize, Result<TextChunk>)> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| TextChunk::is_text_chunk(c))
+        .map(|(i, c)| (i, TextChunk::try_from(c)))
+        .collect()
+}
+
+/// Returns the text chunks with the given keyword
+pub fn get(png: &Png, keyword: &str) -> Vec<TextChunk> {
+    list(png)
+        .into_iter()
+        .filter_map(|(_, t)| t.ok())
+        .filter(|t| t.keyword() == keyword)
+        .collect()
+}
+
+/// Replaces the text chunks with the same keyword, or adds a new one before `IEND`
+///
+/// Returns the index of the chunk.
+pub fn set(png: &mut Png, text: &TextChunk) -> Result<usize> {
+    let chunk = text.to_chunk()?;
+
+    let existing = indices_of(png, text.keyword());
+
+    match existing.split_first() {
+        Some((&first, rest)) => {
+            for &index in rest.iter().rev() {
+                png.remove_chunk_at(index)?;
+            }
+            png.replace_chunk_at(first, chunk)?;
+            Ok(first)
+        }
+        None => png.insert_chunk(chunk, Position::BeforeIend),
+    }
+}
+
+/// Removes all text chunks with the given keyword, returns how many were removed
+pub fn delete(png: &mut Png, keyword: &str) -> Result<usize> {
+    let existing = indices_of(png, keyword);
+
+    for &index in existing.iter().rev() {
+        png.remove_chunk_at(index)?;
+    }
+
+    Ok(existing.len())
+}
+
+/// Matches on the raw keyword, so chunks with a broken text are found too
+fn indices_of(png: &Png, keyword: &str) -> Vec<usize> {
+    let Ok(keyword) = to_latin1(keyword) else {
+        return Vec::new();
+    };
+
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| TextChunk::is_text_chunk(c))
+        .filter(|(_, c)| split_at_null(c.data()).is_ok_and(|(k, _)| k == keyword))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+pub(crate) fn split_at_null(bytes: &[u8]) -> Result<(&[u8], &[u8])> {
+    match bytes.iter().position(|&b| b == 0) {
+        Some(i) => Ok((&bytes[..i], &bytes[i + 1..])),
+        None => bail!("missing null separator"),
+    }
+}
+
+pub(crate) fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| char::from(b)).collect()
+}
+
+fn to_latin1(s: &str) -> Result<Vec<u8>> {
+    s.chars()
+        .map(|c| match u8::try_from(c) {
+            Ok(b) => Ok(b),
+            Err(_) => bail!("{c:?} can't be encoded as Latin-1, use iTXt instead"),
+        })
+        .collect()
+}
+
+/// Keywords are printable Latin-1 without leading, trailing or consecutive spaces
+pub(crate) fn keyword_to_latin1(keyword: &str) -> Result<Vec<u8>> {
+    let bytes = to_latin1(keyword)?;
+
+    if bytes.is_empty() || bytes.len() > 79 {
+        bail!("keyword has to be 1 to 79 bytes long");
+    }
+
+    if !bytes.iter().all(|&b| (32..=126).contains(&b) || b >= 161) {
+        bail!("keyword contains non-printable characters");
+    }
+
+    if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+        bail!("keyword must not have leading, trailing or consecutive spaces");
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = TextChunk::Text {
+            keyword: "Author".to_string(),
+            text: "Jürgen".to_string(),
+        };
+        let chunk = text.to_chunk().unwrap();
+
+        assert_eq!(&chunk.chunk_type().to_string(), "tEXt");
+        assert_eq!(chunk.data(), b"Author\0J\xfcrgen");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_longest_latin1_keyword_round_trip() {
+        // 79 bytes in Latin-1, but 158 in UTF-8
+        let text = TextChunk::Text {
+            keyword: "é".repeat(79),
+            text: "text".to_string(),
+        };
+        let chunk = text.to_chunk().unwrap();
+
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_compressed_text_round_trip() {
+        let text = TextChunk::CompressedText {
+            keyword: "Comment".to_string(),
+            text: "a".repeat(1000),
+        };
+        let chunk = text.to_chunk().unwrap();
+
+        assert_eq!(&chunk.chunk_type().to_string(), "zTXt");
+        assert!(chunk.length() < 100);
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_text_round_trip() {
+        for compressed in [false, true] {
+            let text = TextChunk::InternationalText {
+                keyword: "Title".to_string(),
+                compressed,
+                language_tag: "ja".to_string(),
+                translated_keyword: "タイトル".to_string(),
+                text: "ラスト".to_string(),
+            };
+            let chunk = text.to_chunk().unwrap();
+
+            assert_eq!(&chunk.chunk_type().to_string(), "iTXt");
+            assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_non_latin1_text() {
+        let text = TextChunk::Text {
+            keyword: "Title".to_string(),
+            text: "ラスト".to_string(),
+        };
+        assert!(text.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_invalid_keywords() {
+        for keyword in [
+            "",
+            " Title",
+            "Title ",
+            "Ti  tle",
+            "Ti\ntle",
+            &"a".repeat(80),
+        ] {
+            let text = TextChunk::Text {
+                keyword: keyword.to_string(),
+                text: "text".to_string(),
+            };
+            assert!(text.to_chunk().is_err(), "{keyword:?}");
+        }
+    }
+
+    #[test]
+    fn test_null_in_international_text_fields() {
+        let text = |language_tag: &str, translated_keyword: &str| TextChunk::InternationalText {
+            keyword: "Title".to_string(),
+            compressed: false,
+            language_tag: language_tag.to_string(),
+            translated_keyword: translated_keyword.to_string(),
+            text: "text".to_string(),
+        };
+
+        assert!(text("en\0", "Title").to_chunk().is_err());
+        assert!(text("en", "Ti\0tle").to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_invalid_chunk_data() {
+        let chunk_type = ChunkType::from_str("tEXt").unwrap();
+        assert!(TextChunk::try_from(&Chunk::new(chunk_type, b"no null".to_vec())).is_err());
+
+        let chunk_type = ChunkType::from_str("zTXt").unwrap();
+        let chunk = Chunk::new(chunk_type, b"Comment\0\x01garbage".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_set_get_delete() {
+        let mut png = testing_png();
+        let text = |text: &str| TextChunk::Text {
+            keyword: "Author".to_string(),
+            text: text.to_string(),
+        };
+
+        assert_eq!(set(&mut png, &text("first")).unwrap(), 1);
+        assert_eq!(set(&mut png, &text("second")).unwrap(), 1);
+        assert_eq!(png.chunks().len(), 3);
+        assert_eq!(get(&png, "Author"), vec![text("second")]);
+        assert_eq!(
+            &png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+
+        assert_eq!(list(&png).len(), 1);
+        assert_eq!(delete(&mut png, "Author").unwrap(), 1);
+        assert!(get(&png, "Author").is_empty());
+        assert_eq!(delete(&mut png, "Author").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_set_replaces_duplicates() {
+        let mut png = testing_png();
+        let chunk = TextChunk::Text {
+            keyword: "Author".to_string(),
+            text: "old".to_string(),
+        }
+        .to_chunk()
+        .unwrap();
+        png.insert_chunk(chunk.clone(), Position::BeforeIend)
+            .unwrap();
+        png.insert_chunk(chunk, Position::BeforeIend).unwrap();
+
+        let text = TextChunk::CompressedText {
+            keyword: "Author".to_string(),
+            text: "new".to_string(),
+        };
+        set(&mut png, &text).unwrap();
+
+        assert_eq!(get(&png, "Author"), vec![text]);
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    #[test]
+    fn test_set_delete_unreadable() {
+        let mut png = testing_png();
+        // a zTXt chunk with a stream that doesn't inflate
+        let chunk = Chunk::new(
+            ChunkType::from_str("zTXt").unwrap(),
+            b"Comment\0\0garbage".to_vec(),
+        );
+        png.insert_chunk(chunk.clone(), Position::BeforeIend)
+            .unwrap();
+
+        let text = TextChunk::Text {
+            keyword: "Comment".to_string(),
+            text: "readable".to_string(),
+        };
+        assert_eq!(set(&mut png, &text).unwrap(), 1);
+        assert_eq!(list(&png).len(), 1);
+        assert_eq!(get(&png, "Comment"), vec![text]);
+
+        png.insert_chunk(chunk, Position::BeforeIend).unwrap();
+        assert_eq!(delete(&mut png, "Comment").unwrap(), 2);
+        assert!(list(&png).is_empty());
+    }
+}
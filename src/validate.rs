@@ -0,0 +1,438 @@
+use std::fmt::Display;
+
+use crate::{
+    apng,
+    chunk::Chunk,
+    ihdr::{ColorType, Ihdr},
+    png::Png,
+};
+
+/// Critical chunks defined by the PNG spec
+pub(crate) const KNOWN_CRITICAL: [&str; 4] = ["IHDR", "PLTE", "IDAT", "IEND"];
+
+/// Ancillary chunks the spec allows at most once
+const SINGLE_ANCILLARY: [&str; 11] = [
+    "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "bKGD", "hIST", "tRNS", "pHYs", "tIME", "eXIf",
+];
+
+/// Ancillary chunks that have to come before `PLTE`
+const BEFORE_PLTE: [&str; 5] = ["cHRM", "gAMA", "iCCP", "sBIT", "sRGB"];
+
+/// Ancillary chunks that have to come after `PLTE`
+const AFTER_PLTE: [&str; 3] = ["bKGD", "hIST", "tRNS"];
+
+/// Ancillary chunks that have to come before the first `IDAT`
+const BEFORE_IDAT: [&str; 11] = [
+    "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "bKGD", "hIST", "tRNS", "pHYs", "sPLT", "eXIf",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Allowed to decode, but against the recommendations of the spec
+    Warning,
+    /// Decoders are expected to reject the file
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A single spec violation, `index` points at the offending chunk if there is one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub index: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Violation {
+    pub(crate) fn error(index: impl Into<Option<usize>>, message: impl Into<String>) -> Self {
+        Self {
+            index: index.into(),
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn warning(index: impl Into<Option<usize>>, message: impl Into<String>) -> Self {
+        Self {
+            index: index.into(),
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "{}: chunk {index}: {}", self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+/// Checks the chunk layout of `png` against the PNG spec
+pub fn validate(png: &Png) -> Vec<Violation> {
+    let chunks = png.chunks();
+    let types: Vec<String> = chunks.iter().map(|c| c.chunk_type().to_string()).collect();
+    let find = |chunk_type: &str| types.iter().position(|t| t == chunk_type);
+
+    let mut violations = vec![];
+
+    if chunks.is_empty() {
+        violations.push(Violation::error(None, "PNG has no chunks"));
+        return violations;
+    }
+
+    let ihdr = check_ihdr(chunks, &mut violations);
+    check_iend(&types, chunks, &mut violations);
+    check_idat(&types, &mut violations);
+    check_plte(&types, chunks, ihdr.as_ref(), &mut violations);
+
+    for (index, (chunk, chunk_type)) in chunks.iter().zip(&types).enumerate() {
+        if chunk.chunk_type().is_critical() && !KNOWN_CRITICAL.contains(&chunk_type.as_str()) {
+            violations.push(Violation::error(
+                index,
+                format!("unknown critical chunk {chunk_type}"),
+            ));
+        }
+
+        if !chunk.chunk_type().is_reserved_bit_valid() {
+            violations.push(Violation::error(
+                index,
+                format!("reserved bit of {chunk_type} is set"),
+            ));
+        }
+
+        let is_duplicate = types[..index].contains(chunk_type);
+        if is_duplicate && ["IHDR", "PLTE", "IEND"].contains(&chunk_type.as_str()) {
+            violations.push(Violation::error(index, format!("duplicate {chunk_type}")));
+        } else if is_duplicate && SINGLE_ANCILLARY.contains(&chunk_type.as_str()) {
+            violations.push(Violation::warning(index, format!("duplicate {chunk_type}")));
+        }
+
+        let chunk_type = chunk_type.as_str();
+        if let Some(plte) = find("PLTE") {
+            if BEFORE_PLTE.contains(&chunk_type) && index > plte {
+                violations.push(Violation::error(
+                    index,
+                    format!("{chunk_type} must come before PLTE"),
+                ));
+            }
+
+            if AFTER_PLTE.contains(&chunk_type) && index < plte {
+                violations.push(Violation::error(
+                    index,
+                    format!("{chunk_type} must come after PLTE"),
+                ));
+            }
+        }
+
+        if let Some(idat) = find("IDAT") {
+            if BEFORE_IDAT.contains(&chunk_type) && index > idat {
+                violations.push(Violation::error(
+                    index,
+                    format!("{chunk_type} must come before the first IDAT"),
+                ));
+            }
+        }
+    }
+
+    if let (Some(srgb), Some(_)) = (find("sRGB"), find("iCCP")) {
+        violations.push(Violation::warning(
+            srgb,
+            "sRGB and iCCP should not both be present",
+        ));
+    }
+
+    violations.extend(apng::validate(png));
+    violations.sort_by_key(|v| v.index);
+
+    violations
+}
+
+fn check_ihdr(chunks: &[Chunk], violations: &mut Vec<Violation>) -> Option<Ihdr> {
+    if chunks[0].chunk_type().to_string() != Ihdr::CHUNK_TYPE {
+        violations.push(Violation::error(0, "first chunk must be IHDR"));
+        return None;
+    }
+
+    match Ihdr::try_from(&chunks[0]) {
+        Ok(ihdr) => Some(ihdr),
+        Err(e) => {
+            violations.push(Violation::error(0, format!("invalid IHDR: {e}")));
+            None
+        }
+    }
+}
+
+fn check_iend(types: &[String], chunks: &[Chunk], violations: &mut Vec<Violation>) {
+    let Some(iend) = types.iter().position(|t| t == "IEND") else {
+        violations.push(Violation::error(None, "missing IEND"));
+        return;
+    };
+
+    if chunks[iend].length() != 0 {
+        violations.push(Violation::error(iend, "IEND must be empty"));
+    }
+
+    for (index, chunk_type) in types.iter().enumerate().skip(iend + 1) {
+        if chunk_type != "IEND" {
+            violations.push(Violation::error(index, format!("{chunk_type} after IEND")));
+        }
+    }
+}
+
+fn check_idat(types: &[String], violations: &mut Vec<Violation>) {
+    let idats: Vec<usize> = types
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| *t == "IDAT")
+        .map(|(i, _)| i)
+        .collect();
+
+    if idats.is_empty() {
+        violations.push(Violation::error(None, "missing IDAT"));
+    }
+
+    for pair in idats.windows(2) {
+        if pair[1] != pair[0] + 1 {
+            violations.push(Violation::error(pair[1], "IDAT chunks must be consecutive"));
+        }
+    }
+}
+
+fn check_plte(
+    types: &[String],
+    chunks: &[Chunk],
+    ihdr: Option<&Ihdr>,
+    violations: &mut Vec<Violation>,
+) {
+    let plte = types.iter().position(|t| t == "PLTE");
+    let idat = types.iter().position(|t| t == "IDAT");
+
+    let color_type = ihdr.map(|i| i.color_type);
+
+    let Some(plte) = plte else {
+        if color_type == Some(ColorType::Indexed) {
+            violations.push(Violation::error(None, "indexed images require a PLTE"));
+        }
+        return;
+    };
+
+    if matches!(
+        color_type,
+        Some(ColorType::Grayscale | ColorType::GrayscaleAlpha)
+    ) {
+        violations.push(Violation::error(
+            plte,
+            "PLTE is not allowed for grayscale images",
+        ));
+    }
+
+    if idat.is_some_and(|idat| idat < plte) {
+        violations.push(Violation::error(
+            plte,
+            "PLTE must come before the first IDAT",
+        ));
+    }
+
+    let length = chunks[plte].length();
+    if length == 0 || !length.is_multiple_of(3) || length > 256 * 3 {
+        violations.push(Violation::error(
+            plte,
+            format!("PLTE length {length} is not a multiple of 3 between 3 and 768"),
+        ));
+    } else if let Some(ihdr) = ihdr.filter(|i| i.color_type == ColorType::Indexed) {
+        if length / 3 > 1 << ihdr.bit_depth {
+            violations.push(Violation::error(
+                plte,
+                format!(
+                    "PLTE has more entries than bit depth {} allows",
+                    ihdr.bit_depth
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chunk_type::ChunkType, ihdr::InterlaceMethod};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn ihdr(color_type: ColorType) -> Chunk {
+        Ihdr {
+            width: 1,
+            height: 1,
+            bit_depth: 8,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: InterlaceMethod::None,
+        }
+        .to_chunk()
+    }
+
+    fn has_error(violations: &[Violation], index: Option<usize>) -> bool {
+        violations
+            .iter()
+            .any(|v| v.severity == Severity::Error && v.index == index)
+    }
+
+    #[test]
+    fn test_valid_png() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("IDAT", &[]),
+            chunk("IDAT", &[]),
+            chunk("ruSt", b"hey"),
+            chunk("IEND", &[]),
+        ]);
+
+        assert_eq!(validate(&png), vec![]);
+    }
+
+    #[test]
+    fn test_ihdr_not_first() {
+        let png = Png::from_chunks(vec![
+            chunk("IDAT", &[]),
+            ihdr(ColorType::Rgb),
+            chunk("IEND", &[]),
+        ]);
+
+        assert!(has_error(&validate(&png), Some(0)));
+    }
+
+    #[test]
+    fn test_chunk_after_iend() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+            chunk("ruSt", b"hey"),
+        ]);
+
+        assert!(has_error(&validate(&png), Some(3)));
+    }
+
+    #[test]
+    fn test_missing_iend_and_idat() {
+        let png = Png::from_chunks(vec![ihdr(ColorType::Rgb)]);
+
+        let violations = validate(&png);
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().all(|v| v.index.is_none()));
+    }
+
+    #[test]
+    fn test_idat_not_consecutive() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("IDAT", &[]),
+            chunk("ruSt", b"hey"),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+
+        assert!(has_error(&validate(&png), Some(3)));
+    }
+
+    #[test]
+    fn test_plte_rules() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Indexed),
+            chunk("IDAT", &[]),
+            chunk("PLTE", &[0, 0, 0]),
+            chunk("IEND", &[]),
+        ]);
+        assert!(has_error(&validate(&png), Some(2)));
+
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Indexed),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        assert!(has_error(&validate(&png), None));
+
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Grayscale),
+            chunk("PLTE", &[0, 0, 0]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        assert!(has_error(&validate(&png), Some(1)));
+
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("PLTE", &[0, 0]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        assert!(has_error(&validate(&png), Some(1)));
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            ihdr(ColorType::Rgb),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+
+        let violations = validate(&png);
+        assert!(has_error(&violations, Some(1)));
+        assert!(violations
+            .iter()
+            .any(|v| v.severity == Severity::Warning && v.index == Some(3)));
+    }
+
+    #[test]
+    fn test_ancillary_ordering() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("IDAT", &[]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("IEND", &[]),
+        ]);
+
+        assert!(has_error(&validate(&png), Some(2)));
+    }
+
+    #[test]
+    fn test_unknown_critical_chunk() {
+        let png = Png::from_chunks(vec![
+            ihdr(ColorType::Rgb),
+            chunk("RuSt", b"hey"),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+
+        assert!(has_error(&validate(&png), Some(1)));
+    }
+
+    #[test]
+    fn test_violation_display() {
+        let violation = Violation::error(2, "IDAT chunks must be consecutive");
+        assert_eq!(
+            violation.to_string(),
+            "error: chunk 2: IDAT chunks must be consecutive"
+        );
+    }
+}
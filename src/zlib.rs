@@ -0,0 +1,55 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, Result};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+/// Deflates `bytes` into a zlib stream
+pub fn compress(bytes: &[u8], level: Compression) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    encoder.write_all(bytes)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Inflates a zlib stream, failing if it holds more than `limit` bytes
+///
+/// A few bytes of crafted data can inflate to gigabytes, so the output is
+/// never read past the limit.
+pub fn decompress(bytes: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let mut result = Vec::new();
+    ZlibDecoder::new(bytes)
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(&mut result)?;
+
+    if result.len() > limit {
+        bail!("decompressed data is larger than {limit} bytes");
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = b"a message, a message, a message".repeat(10);
+        let compressed = compress(&bytes, Compression::best()).unwrap();
+
+        assert!(compressed.len() < bytes.len());
+        assert_eq!(decompress(&compressed, bytes.len()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_decompress_over_limit() {
+        let compressed = compress(&[0; 1000], Compression::best()).unwrap();
+
+        assert!(decompress(&compressed, 999).is_err());
+    }
+
+    #[test]
+    fn test_decompress_invalid_stream() {
+        assert!(decompress(b"not zlib", 100).is_err());
+    }
+}